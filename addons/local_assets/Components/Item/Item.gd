@@ -65,7 +65,7 @@ func _on_path_pressed():
 
 
 func _on_import_pressed():
-	root.copy_asset(asset_path, asset_name)
+	root.copy_asset(asset_path, asset_name, asset.get("id", -1))
 
 
 func make_icon(source_icon: Image) -> ImageTexture:
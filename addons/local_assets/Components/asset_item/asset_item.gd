@@ -64,4 +64,4 @@ func _on_path_pressed():
 
 
 func _on_import_pressed():
-	root.copy_file(asset_path, asset_name)
+	root.copy_file(asset_path, asset_name, id)
@@ -12,6 +12,9 @@ func _enter_tree():
 	command_palette.add_command(
 		"Reset DB", "localAssets/Reset_db", Callable(main_panel_instance, "_reset_db")
 	)
+	command_palette.add_command(
+		"Check DB", "localAssets/Check_db", Callable(main_panel_instance, "_check_db")
+	)
 	if OS.get_name() == "Linux":
 		command_palette.add_command("Add template", "localAssets/config_template", _add_template)
 		command_palette.add_command(
@@ -26,6 +29,7 @@ func _exit_tree():
 	if main_panel_instance:
 		EditorInterface.get_editor_main_screen().remove_child(main_panel_instance)
 		command_palette.remove_command("localAssets/Reset_db")
+		command_palette.remove_command("localAssets/Check_db")
 		if OS.get_name() == "Linux":
 			command_palette.remove_command("localAssets/config_template")
 			command_palette.remove_command("localAssets/remove_config_template")
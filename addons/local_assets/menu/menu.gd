@@ -12,6 +12,15 @@ var editor_settings: EditorSettings = EditorInterface.get_editor_settings()
 var file_names: PackedStringArray
 var use_first_image: bool
 var use_folder_name: bool
+var parallel_scan: bool
+var sort_search_by_relevance: bool = true
+var fuzzy_search: bool
+var sort_order: String = "name"
+var sort_descending: bool
+var watch_asset_dir: bool
+var ignore_patterns: PackedStringArray
+var _watched_path: String
+var _last_scan_summary: Dictionary
 var use_uniform_image_size: bool
 var uniform_image_size: Vector2i
 var asset_manager: AssetManager
@@ -23,6 +32,9 @@ var _scanned_modes: Dictionary = {}
 var model_previewer: LocalAssetsModelPreviewer
 var preview_window: LocalAssetsPreviewWindow
 
+## Emitted once the background scan started by [method _scan_assets] is over.
+signal scan_done(ok: bool)
+
 @onready var files_dialog: FileDialog = $FileDialog
 @onready var asset_path_edit: LineEdit = %AssetsPath
 @onready var grid: GridContainer = %GridContainer
@@ -46,11 +58,26 @@ func _ready():
 	_on_editor_settings_changed()
 
 	asset_manager = AssetManager.new_db(db_path)
+	# The setters below reset get_error(), so keep how opening the database went.
+	var open_error := asset_manager.get_error()
+	_connect_scan_signals()
 
 	if not file_names.is_empty():
 		asset_manager.set_preview_file_names(file_names)
 	asset_manager.set_use_first_image(use_first_image)
 	asset_manager.set_use_folder_name(use_folder_name)
+	asset_manager.set_parallel_scan(parallel_scan)
+	asset_manager.set_sort_by_relevance(sort_search_by_relevance)
+	asset_manager.set_fuzzy_search(fuzzy_search)
+	asset_manager.set_sort_order(sort_order, sort_descending)
+	asset_manager.set_ignore_patterns(ignore_patterns)
+	if open_error == ERR_FILE_CORRUPT:
+		await _recover_db(false)
+	elif open_error == ERR_FILE_UNRECOGNIZED:
+		push_error(
+			"[Local Assets]: %s was written by a newer version of Local Assets. Update the plugin to use it." % db_path
+		)
+	_update_watcher()
 
 	if not asset_path_edit.text.is_empty():
 		var start = Time.get_ticks_msec()
@@ -81,6 +108,39 @@ func _on_editor_settings_changed():
 		use_folder_name = editor_settings.get_setting("Local_Assets/use_folder_name")
 		if asset_manager:
 			asset_manager.set_use_folder_name(use_folder_name)
+	if editor_settings.has_setting("Local_Assets/ignore_patterns"):
+		ignore_patterns = editor_settings.get_setting("Local_Assets/ignore_patterns")
+		if asset_manager:
+			asset_manager.set_ignore_patterns(ignore_patterns)
+	if editor_settings.has_setting("Local_Assets/parallel_scan"):
+		parallel_scan = editor_settings.get_setting("Local_Assets/parallel_scan")
+		if asset_manager:
+			asset_manager.set_parallel_scan(parallel_scan)
+	if editor_settings.has_setting("Local_Assets/sort_search_by_relevance"):
+		sort_search_by_relevance = editor_settings.get_setting("Local_Assets/sort_search_by_relevance")
+		if asset_manager:
+			asset_manager.set_sort_by_relevance(sort_search_by_relevance)
+	if editor_settings.has_setting("Local_Assets/fuzzy_search"):
+		fuzzy_search = editor_settings.get_setting("Local_Assets/fuzzy_search")
+		if asset_manager:
+			asset_manager.set_fuzzy_search(fuzzy_search)
+	if (
+		editor_settings.has_setting("Local_Assets/sort_order")
+		and editor_settings.has_setting("Local_Assets/sort_descending")
+	):
+		var new_order: String = editor_settings.get_setting("Local_Assets/sort_order")
+		var new_descending: bool = editor_settings.get_setting("Local_Assets/sort_descending")
+		if new_order != sort_order or new_descending != sort_descending:
+			sort_order = new_order
+			sort_descending = new_descending
+			if asset_manager:
+				asset_manager.set_sort_order(sort_order, sort_descending)
+				clear_items()
+				load_assets()
+	if editor_settings.has_setting("Local_Assets/watch_asset_dir"):
+		watch_asset_dir = editor_settings.get_setting("Local_Assets/watch_asset_dir")
+		if asset_manager:
+			_update_watcher()
 	if editor_settings.has_setting("Local_Assets/use_uniform_image_size"):
 		use_uniform_image_size = editor_settings.get_setting("Local_Assets/use_uniform_image_size")
 	if editor_settings.has_setting("Local_Assets/uniform_image_size"):
@@ -162,6 +222,24 @@ func _setup_settings():
 		_set_editor_setting("Local_Assets/use_first_image_found", false, TYPE_BOOL)
 	if not editor_settings.has_setting("Local_Assets/use_folder_name"):
 		_set_editor_setting("Local_Assets/use_folder_name", true, TYPE_BOOL)
+	if not editor_settings.has_setting("Local_Assets/ignore_patterns"):
+		_set_editor_setting(
+			"Local_Assets/ignore_patterns",
+			PackedStringArray([".git/", "node_modules/"]),
+			TYPE_PACKED_STRING_ARRAY
+		)
+	if not editor_settings.has_setting("Local_Assets/parallel_scan"):
+		_set_editor_setting("Local_Assets/parallel_scan", false, TYPE_BOOL)
+	if not editor_settings.has_setting("Local_Assets/sort_search_by_relevance"):
+		_set_editor_setting("Local_Assets/sort_search_by_relevance", true, TYPE_BOOL)
+	if not editor_settings.has_setting("Local_Assets/fuzzy_search"):
+		_set_editor_setting("Local_Assets/fuzzy_search", false, TYPE_BOOL)
+	if not editor_settings.has_setting("Local_Assets/sort_order"):
+		_set_editor_setting("Local_Assets/sort_order", "name", TYPE_STRING)
+	if not editor_settings.has_setting("Local_Assets/sort_descending"):
+		_set_editor_setting("Local_Assets/sort_descending", false, TYPE_BOOL)
+	if not editor_settings.has_setting("Local_Assets/watch_asset_dir"):
+		_set_editor_setting("Local_Assets/watch_asset_dir", false, TYPE_BOOL)
 	if not editor_settings.has_setting("Local_Assets/use_uniform_image_size"):
 		_set_editor_setting("Local_Assets/use_uniform_image_size", false, TYPE_BOOL)
 	if not editor_settings.has_setting("Local_Assets/uniform_image_size"):
@@ -186,6 +264,8 @@ func _on_open_dir_pressed():
 
 
 func _on_assets_path_changed(new_text: String):
+	_cancel_scan()
+	_update_watcher()
 	_scanned_modes.clear()
 	clear_items()
 	if new_text.is_empty():
@@ -207,6 +287,11 @@ func search(search_string: String):
 
 	var results = _search_page(search_string, 1)
 
+	if asset_manager.get_error() == ERR_PARSE_ERROR:
+		background_text.text = "Invalid search: %s" % results.error
+		background_text.show()
+		return
+
 	if asset_manager.get_error() != OK:
 		background_text.text = "Search failed"
 		background_text.show()
@@ -239,16 +324,21 @@ func load_assets(scan: bool = true):
 	background_text.show()
 
 	if scan:
-		# The AssetManager extension must only be called from the main thread.
-		# Running the scan on a Thread let the instance be freed mid-call, which
-		# crashes godot-rust ("Destroyed an object ... while a bind() was active").
-		await get_tree().process_frame
+		# The scan runs on a worker thread inside the extension; wait for it to report back.
 		if asset_manager == null:
 			return
-		_scan_assets(asset_path_edit.text)
+		while asset_manager.is_scanning():
+			await scan_done
+		var scan_mode := view_mode
+		if not _scan_assets(asset_path_edit.text):
+			background_text.text = "Failed to scan directory"
+			return
+		var ok: bool = await scan_done
+		if asset_manager == null or view_mode != scan_mode:
+			return
 		_scanned_modes[view_mode] = true
 
-		if asset_manager.get_error() != OK:
+		if not ok:
 			background_text.text = "Failed to scan directory"
 			return
 
@@ -271,11 +361,94 @@ func load_assets(scan: bool = true):
 		background_text.text = "Failed to load assets"
 
 
-func _scan_assets(path: String):
+func _scan_assets(path: String) -> bool:
+	_set_browser_root(path)
+	var err: Error
 	if view_mode == ViewMode.INDIVIDUAL:
-		asset_manager.find_individual_assets(path, PackedStringArray(INDIVIDUAL_ASSET_EXTENSIONS))
+		err = asset_manager.start_individual_scan(path, PackedStringArray(INDIVIDUAL_ASSET_EXTENSIONS))
+	else:
+		err = asset_manager.start_scan(path)
+	return err == OK
+
+
+## Register [param path] as the library root the browser shows, so its assets are linked to it. Only a change
+## of folder touches the roots: the root of the folder shown before is disabled, so it stops adding its assets
+## to searches, and comes back if the browser returns to it. Roots added from code are left alone.
+func _set_browser_root(path: String):
+	var previous: String = editor_settings.get_project_metadata("local_assets", "browser_root", "")
+	if path == previous:
+		return
+	if not previous.is_empty():
+		var previous_root := ProjectSettings.globalize_path(previous).rstrip("/\\")
+		for root in asset_manager.list_roots():
+			if root.path == previous_root:
+				asset_manager.set_root_enabled(root.id, false)
+	var root_id: int = asset_manager.add_root(path, "")
+	if root_id >= 0:
+		asset_manager.set_root_enabled(root_id, true)
+		editor_settings.set_project_metadata("local_assets", "browser_root", path)
+
+
+func _connect_scan_signals():
+	asset_manager.scan_progress.connect(_on_scan_progress)
+	asset_manager.scan_finished.connect(_on_scan_finished)
+	asset_manager.scan_failed.connect(_on_scan_failed)
+	asset_manager.scan_cancelled.connect(_on_scan_cancelled)
+	asset_manager.assets_changed.connect(_on_assets_changed)
+
+
+func _on_scan_progress(dirs_visited: int, assets_found: int):
+	background_text.text = "Loading... (%d folders, %d assets)" % [dirs_visited, assets_found]
+
+
+func _on_scan_finished(summary: Dictionary):
+	print_verbose("[Local Assets]: scan finished: ", summary)
+	_last_scan_summary = summary
+	for failure in summary.get("parse_failures", []):
+		push_warning("[Local Assets]: could not parse %s: %s" % [failure.path, failure.error])
+	scan_done.emit(true)
+
+
+func _update_watcher():
+	if asset_manager == null:
+		return
+	var path := asset_path_edit.text if watch_asset_dir else ""
+	if path == _watched_path and asset_manager.is_watching() == not path.is_empty():
+		return
+	_watched_path = path
+	if path.is_empty():
+		asset_manager.stop_watching()
 	else:
-		asset_manager.find_assets(path)
+		asset_manager.start_watching(path, PackedStringArray(INDIVIDUAL_ASSET_EXTENSIONS))
+
+
+func _on_assets_changed():
+	if asset_manager == null or asset_manager.is_scanning():
+		return
+	if not %Search.text.is_empty():
+		search(%Search.text)
+		return
+	var total_pages := _get_total_pages()
+	var current_page: int = clampi(%PaginationBar.current_page, 1, maxi(total_pages, 1))
+	update_pagination_bars(total_pages, current_page)
+	_on_pagination_bar_page_changed(current_page)
+	background_text.hide()
+	grid.show()
+
+
+func _on_scan_cancelled(summary: Dictionary):
+	print_verbose("[Local Assets]: scan cancelled: ", summary)
+	scan_done.emit(false)
+
+
+func _cancel_scan():
+	if asset_manager and asset_manager.is_scanning():
+		asset_manager.cancel_scan(false)
+
+
+func _on_scan_failed(message: String):
+	push_error("[Local Assets]: scan failed: " + message)
+	scan_done.emit(false)
 
 
 func add_items(items: Array):
@@ -337,14 +510,16 @@ func _notification(what: int) -> void:
 			asset_manager = null
 
 
-func copy_asset(dir: String, name: String):
+func copy_asset(dir: String, name: String, id: int = -1):
 	var thread = Thread.new()
 	thread.start(LocalAssetsAssetCopier.copy_assets.bind(dir, "res://Assets/%s" % name))
 	await _wait_for_thread(thread)
+	if id >= 0 and asset_manager:
+		asset_manager.mark_imported(id)
 	EditorInterface.get_resource_filesystem().scan()
 
 
-func copy_file(src_path: String, _name: String = ""):
+func copy_file(src_path: String, _name: String = "", id: int = -1):
 	var dst_path := "res://Assets/%s" % src_path.get_file()
 	var thread := Thread.new()
 	if src_path.get_extension().to_lower() == "gltf":
@@ -352,6 +527,8 @@ func copy_file(src_path: String, _name: String = ""):
 	else:
 		thread.start(LocalAssetsAssetCopier.copy_file.bind(src_path, dst_path))
 	await _wait_for_thread(thread)
+	if id >= 0 and asset_manager:
+		asset_manager.mark_individual_imported(id)
 	EditorInterface.get_resource_filesystem().scan()
 
 
@@ -364,6 +541,7 @@ func _wait_for_thread(thread: Thread) -> Variant:
 
 
 func _reset_db():
+	_cancel_scan()
 	asset_manager = null
 
 	await get_tree().process_frame
@@ -374,14 +552,26 @@ func _reset_db():
 		if err != OK:
 			push_error("Failed to delete database: " + str(err))
 			return
+	# Write-ahead log and its index, left behind if the editor didn't close the database cleanly.
+	for suffix in ["-wal", "-shm"]:
+		if FileAccess.file_exists(db_path + suffix):
+			DirAccess.remove_absolute(db_path + suffix)
 
 	await get_tree().process_frame
 
 	asset_manager = AssetManager.new_db(db_path)
+	_connect_scan_signals()
 	asset_manager.set_preview_file_names(file_names)
 	asset_manager.set_use_first_image(use_first_image)
 	asset_manager.set_use_folder_name(use_folder_name)
+	asset_manager.set_parallel_scan(parallel_scan)
+	asset_manager.set_sort_by_relevance(sort_search_by_relevance)
+	asset_manager.set_fuzzy_search(fuzzy_search)
+	asset_manager.set_sort_order(sort_order, sort_descending)
+	asset_manager.set_ignore_patterns(ignore_patterns)
 	asset_manager.set_page_size(page_size)
+	_watched_path = ""
+	_update_watcher()
 
 	_scanned_modes.clear()
 	clear_items()
@@ -391,6 +581,72 @@ func _reset_db():
 	print("Database reset complete")
 
 
+## Check the database for damage, and rebuild it if the file is damaged.
+func _check_db():
+	var report: Dictionary = asset_manager.check_integrity()
+	if report.ok:
+		print("Database check found no problems")
+		return
+	for error in report.errors:
+		push_warning("[Local Assets]: database check: " + error)
+	for error in report.foreign_key_errors:
+		push_warning(
+			"[Local Assets]: database check: %s row %d refers to a missing %s row"
+			% [error.table, error.rowid, error.parent]
+		)
+	if report.damaged:
+		await _recover_db(true)
+		_update_watcher()
+		_scanned_modes.clear()
+		clear_items()
+		if not asset_path_edit.text.is_empty():
+			load_assets()
+
+
+## Move a damaged database aside and rebuild it from the library roots and their Asset.json files,
+## waiting for the scan that fills it. Without [param force] it is only rebuilt if
+## [method AssetManager.recover_database] finds it damaged too.
+func _recover_db(force: bool):
+	_cancel_scan()
+	while asset_manager.is_scanning():
+		await get_tree().process_frame
+	asset_manager.stop_watching()
+	_watched_path = ""
+
+	var report: Dictionary = asset_manager.recover_database(
+		PackedStringArray(INDIVIDUAL_ASSET_EXTENSIONS), force
+	)
+	if asset_manager.get_error() != OK:
+		push_error(
+			"[Local Assets]: could not rebuild the damaged database: "
+			+ error_string(asset_manager.get_error())
+		)
+		return
+	background_text.text = "Rebuilding the database..."
+	background_text.show()
+	var finished: bool = await scan_done
+	if not finished:
+		push_warning(
+			(
+				"[Local Assets]: the database was damaged and has been replaced, but scanning its %d library roots didn't finish. Scan again to find the assets. The damaged file was kept as %s"
+				% [report.roots_restored, report.backup_path]
+			)
+		)
+		return
+	push_warning(
+		(
+			"[Local Assets]: the database was damaged and has been rebuilt. Recovered %d assets (%d from Asset.json files) and %d individual assets from %d library roots. The damaged file was kept as %s"
+			% [
+				_last_scan_summary.get("assets_recovered", 0),
+				_last_scan_summary.get("from_asset_json", 0),
+				_last_scan_summary.get("individual_assets_recovered", 0),
+				report.roots_restored,
+				report.backup_path,
+			]
+		)
+	)
+
+
 func update_pagination_bars(total_pages: int, current_page: int = 1):
 	var pagebars = get_tree().get_nodes_in_group("PageBarLocalAssets_sdlakjf")
 	for bar: LocalAssetsPaginationBar in pagebars:
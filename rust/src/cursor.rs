@@ -0,0 +1,171 @@
+//! Keyset cursors: where a batch of assets ended, so the next batch starts right after that row
+//! instead of skipping rows with OFFSET. Rows added or removed in between don't shift later batches,
+//! and deep batches are as fast as the first.
+
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::sort::SortTerm;
+
+/// Order of searches ranked by relevance, see `sort::relevance_terms`.
+pub(crate) const RELEVANCE_ORDER: &str = "relevance";
+
+/// Order of fuzzy search results: score, then name and id.
+pub(crate) const FUZZY_ORDER: &str = "fuzzy";
+
+/// The sort values of the last row of a batch, and the order and listing they belong to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Cursor {
+    pub order: String,
+    /// `scope_hash` of the listing the batch came from.
+    scope: u64,
+    after: Vec<serde_json::Value>,
+}
+
+/// Hash of what a listing shows besides where a batch starts (table, query, sort order, root filter),
+/// so a cursor can't continue a different one. FNV-1a, the same on every platform and build.
+pub(crate) fn scope_hash(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        // The separator keeps ("ab", "c") and ("a", "bc") apart.
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+impl Cursor {
+    pub fn new(order: impl Into<String>, values: &[Value]) -> Self {
+        let after = values
+            .iter()
+            .map(|value| match value {
+                Value::Integer(i) => serde_json::Value::from(*i),
+                Value::Real(f) => serde_json::Value::from(*f),
+                Value::Text(s) => serde_json::Value::from(s.as_str()),
+                Value::Null | Value::Blob(_) => serde_json::Value::Null,
+            })
+            .collect();
+        Self {
+            order: order.into(),
+            scope: 0,
+            after,
+        }
+    }
+
+    /// The cursor for the listing with `scope_hash` [scope].
+    pub fn scoped(self, scope: u64) -> Self {
+        Self { scope, ..self }
+    }
+
+    pub fn scope(&self) -> u64 {
+        self.scope
+    }
+
+    /// The cursor as a string for scripts to hand back. Its contents are not part of the API.
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// None if [text] isn't a string made by `encode`.
+    pub fn decode(text: &str) -> Option<Self> {
+        serde_json::from_str(text).ok()
+    }
+
+    /// The sort values, if the cursor was made for [order] and has one for each of [count] terms.
+    pub fn values(&self, order: &str, count: usize) -> Option<Vec<Value>> {
+        if self.order != order || self.after.len() != count {
+            return None;
+        }
+        self.after
+            .iter()
+            .map(|value| match value {
+                serde_json::Value::Null => Some(Value::Null),
+                serde_json::Value::String(s) => Some(Value::Text(s.clone())),
+                serde_json::Value::Number(n) => match n.as_i64() {
+                    Some(i) => Some(Value::Integer(i)),
+                    None => n.as_f64().map(Value::Real),
+                },
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// SQL condition for the rows after [values] in the order of [terms]. Each value is referenced as
+/// parameter [first_param] + its index, so they must be bound in that order.
+///
+/// A row is after the cursor if it's after it on some term and equal on all the terms before. NULLs
+/// come last, so a row with a NULL is after every value but never after a NULL.
+pub(crate) fn after_clause(terms: &[SortTerm], values: &[Value], first_param: usize) -> String {
+    let mut alternatives = Vec::new();
+    let mut equal: Vec<String> = Vec::new();
+    for (i, (term, value)) in terms.iter().zip(values).enumerate() {
+        let param = first_param + i;
+        if *value == Value::Null {
+            equal.push(format!("{} IS NULL", term.expression));
+            continue;
+        }
+
+        let operator = if term.descending { "<" } else { ">" };
+        let mut after = format!("{} {} ?{}", term.expression, operator, param);
+        if term.nullable {
+            after = format!("({} OR {} IS NULL)", after, term.expression);
+        }
+        alternatives.push(equal.iter().cloned().chain(std::iter::once(after)).collect::<Vec<_>>().join(" AND "));
+        equal.push(format!("{} = ?{}", term.expression, param));
+    }
+
+    if alternatives.is_empty() {
+        "0".to_string()
+    } else {
+        format!("(({}))", alternatives.join(") OR ("))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip_exactly() {
+        let values = vec![
+            Value::Real(-7.364_512_983_104_512e-6),
+            Value::Real(0.1 + 0.2),
+            Value::Real(f64::MIN_POSITIVE),
+            Value::Real(3.0),
+            Value::Integer(i64::MAX),
+            Value::Text("Forest Kit".to_string()),
+            Value::Null,
+        ];
+        let cursor = Cursor::decode(&Cursor::new(RELEVANCE_ORDER, &values).encode()).unwrap();
+        assert_eq!(cursor.values(RELEVANCE_ORDER, values.len()), Some(values.clone()));
+        assert_eq!(cursor.values(FUZZY_ORDER, values.len()), None);
+        assert_eq!(cursor.values(RELEVANCE_ORDER, 1), None);
+    }
+
+    #[test]
+    fn cursors_remember_their_listing() {
+        let scope = scope_hash(&["assets", "tree", "name:asc", ""]);
+        assert_ne!(scope, scope_hash(&["assets", "rock", "name:asc", ""]));
+        assert_ne!(scope, scope_hash(&["assets", "tree", "name:desc", ""]));
+        assert_ne!(scope, scope_hash(&["assets", "tree", "name:asc", "1"]));
+        assert_ne!(scope, scope_hash(&["individual_assets", "tree", "name:asc", ""]));
+        assert_ne!(scope_hash(&["ab", "c"]), scope_hash(&["a", "bc"]));
+
+        let cursor = Cursor::new(RELEVANCE_ORDER, &[Value::Integer(1)]).scoped(scope);
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap().scope(), scope);
+    }
+
+    #[test]
+    fn bm25_like_reals_round_trip() {
+        // Walk through a spread of bit patterns; without lossless parsing some come back one ULP off.
+        let mut f = -1.0e-6f64;
+        for _ in 0..10_000 {
+            f = f64::from_bits(f.to_bits() + 7_919_113);
+            let cursor = Cursor::decode(&Cursor::new(FUZZY_ORDER, &[Value::Real(f)]).encode()).unwrap();
+            assert_eq!(cursor.values(FUZZY_ORDER, 1), Some(vec![Value::Real(f)]));
+        }
+    }
+}
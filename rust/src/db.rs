@@ -0,0 +1,99 @@
+//! Opening the database. Every connection gets the same settings, and the `AssetManager` keeps its
+//! connections open between calls instead of reopening the file for each one.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{Connection, Result as SqlResult};
+
+/// How long a statement waits for another connection (a background scan, say) to release the write lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Prepared statements each connection keeps for `prepare_cached`.
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// Idle connections a pool keeps open.
+const MAX_IDLE: usize = 4;
+
+/// Open the database at [path] in WAL mode, so reads don't wait for a scan's writes and a crash
+/// mid-write leaves the file intact, losing at most the transactions that hadn't committed.
+pub(crate) fn open(path: &str) -> SqlResult<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    // With WAL, NORMAL only syncs at checkpoints: a power cut can lose the last commits, not corrupt the file.
+    conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+    Ok(conn)
+}
+
+/// Connections to one database, reused between calls. A call that needs a connection while another
+/// is in use gets a new one.
+pub(crate) struct Pool {
+    path: String,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl Pool {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// An idle connection, or a new one if there is none. It goes back to the pool when dropped.
+    pub fn get(&self) -> SqlResult<PooledConnection<'_>> {
+        let idle = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+        let conn = match idle {
+            Some(conn) => conn,
+            None => open(&self.path)?,
+        };
+        Ok(PooledConnection {
+            pool: self,
+            conn: Some(conn),
+        })
+    }
+
+    /// Close the idle connections, e.g. before the file is moved. Connections in use are not affected.
+    pub fn close_idle(&self) {
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.clear();
+        }
+    }
+}
+
+/// A connection borrowed from a [Pool].
+pub(crate) struct PooledConnection<'a> {
+    pool: &'a Pool,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection is only taken on drop")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection is only taken on drop")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else { return };
+        // A connection left inside a transaction is closed, which rolls it back.
+        if !conn.is_autocommit() {
+            return;
+        }
+        if let Ok(mut idle) = self.pool.idle.lock() {
+            if idle.len() < MAX_IDLE {
+                idle.push(conn);
+            }
+        }
+    }
+}
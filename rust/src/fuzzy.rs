@@ -0,0 +1,117 @@
+//! Typo-tolerant matching, used when a search finds nothing or fuzzy search is turned on.
+//!
+//! Candidates come from the trigram index (see `search_index::trigram_expression`) and are scored here
+//! by edit distance between the query words and the words of their name and tags.
+
+use crate::search_key::{search_key, split_tokens};
+
+/// Lowest score a fuzzy result can have.
+pub(crate) const MIN_SCORE: f64 = 0.6;
+
+/// Most rows taken from the trigram index to be scored.
+pub(crate) const MAX_CANDIDATES: i64 = 500;
+
+/// Optimal string alignment distance: insertions, deletions, substitutions and swaps of two
+/// neighbouring letters each count as one edit.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// How close [word] is to [candidate], from 0 to 1. A candidate starting with [word] matches fully,
+/// like a plain search term does.
+fn word_similarity(word: &str, candidate: &str) -> f64 {
+    if candidate.starts_with(word) {
+        return 1.0;
+    }
+    let word: Vec<char> = word.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+    let longest = word.len().max(candidate.len());
+    1.0 - edit_distance(&word, &candidate) as f64 / longest as f64
+}
+
+/// Words of [text] as search keys, with the parts of compound words like "RedTreeLarge".
+fn split_words(text: &str) -> Vec<String> {
+    let tokens = split_tokens(text);
+    search_key(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .chain(tokens.split(' '))
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// How well an asset named [name] with [tags] matches [words], from 0 to 1: the best similarity of
+/// each word to a word of the name or tags, averaged over [words]. [words] must be search keys.
+pub(crate) fn score(words: &[String], name: &str, tags: &[String]) -> f64 {
+    if words.is_empty() {
+        return 0.0;
+    }
+
+    let candidates: Vec<String> = split_words(name).into_iter().chain(tags.iter().flat_map(|t| split_words(t))).collect();
+    let total: f64 = words
+        .iter()
+        .map(|word| {
+            candidates
+                .iter()
+                .map(|candidate| word_similarity(word, candidate))
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    total / words.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str) -> usize {
+        edit_distance(&a.chars().collect::<Vec<_>>(), &b.chars().collect::<Vec<_>>())
+    }
+
+    /// The words a search for [query] is scored by.
+    fn words(query: &str) -> Vec<String> {
+        crate::query::parse(query).unwrap().unwrap().fuzzy_words()
+    }
+
+    #[test]
+    fn edit_distance_counts_swaps_as_one_edit() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("knight", "knight"), 0);
+        assert_eq!(distance("knigt", "knight"), 1);
+        assert_eq!(distance("platfromer", "platformer"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        // Optimal string alignment: a swapped pair can't be edited again.
+        assert_eq!(distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn typos_score_above_the_cutoff_and_unrelated_words_below() {
+        assert!(score(&words("platfromer"), "Platformer Kit", &[]) >= MIN_SCORE);
+        assert!(score(&words("knigt"), "Medieval", &["Knight".to_string()]) >= MIN_SCORE);
+        assert!(score(&words("grass"), "Forest", &["Nature".to_string()]) < MIN_SCORE);
+        assert!(score(&words("tree"), "Rock", &[]) < MIN_SCORE);
+
+        // Prefixes match fully, compound names are split, and every query word counts.
+        assert_eq!(score(&words("plat"), "Platformer", &[]), 1.0);
+        assert_eq!(score(&words("tree large"), "RedTreeLarge", &[]), 1.0);
+        assert!(score(&words("tree zebra"), "RedTreeLarge", &[]) < score(&words("tree"), "RedTreeLarge", &[]));
+        assert_eq!(score(&[], "Anything", &[]), 0.0);
+    }
+}
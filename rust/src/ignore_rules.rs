@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+/// Name of the per-directory ignore file. Uses the same syntax as .gitignore.
+pub(crate) const IGNORE_FILE_NAME: &str = ".assetignore";
+
+/// Decides which paths a scan skips: `.assetignore` files plus a global pattern list.
+///
+/// Like git, the closest `.assetignore` that has an opinion about a path wins, so a deeper file can
+/// re-include (`!pattern`) what a parent excluded. `.assetignore` files above the scanned directory
+/// count too. Global patterns are relative to the [root] given to `new` and are checked last; a partial
+/// rescan (e.g. from the watcher) only matches a full scan if it passes the library root, not the
+/// folder it starts at.
+///
+/// Cloning is cheap (the parsed matchers are shared), so parallel workers each carry their own copy
+/// instead of locking one cache.
+#[derive(Clone)]
+pub(crate) struct IgnoreRules {
+    root: PathBuf,
+    global: Arc<Gitignore>,
+    per_dir: HashMap<PathBuf, Option<Arc<Gitignore>>>,
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path, global_patterns: &[String]) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in global_patterns {
+            // A bad pattern only loses itself, not the rest of the list.
+            let _ = builder.add_line(None, pattern);
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            global: Arc::new(builder.build().unwrap_or_else(|_| Gitignore::empty())),
+            per_dir: HashMap::new(),
+        }
+    }
+
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            if let Some(matcher) = self.matcher_for(current) {
+                match matcher.matched_path_or_any_parents(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            dir = current.parent();
+        }
+
+        // The root itself is never ignored, and the matcher can't strip it from a path outside it.
+        path != self.root && path.starts_with(&self.root) && self.global.matched_path_or_any_parents(path, is_dir).is_ignore()
+    }
+
+    fn matcher_for(&mut self, dir: &Path) -> Option<&Gitignore> {
+        self.per_dir
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let file = dir.join(IGNORE_FILE_NAME);
+                if !file.is_file() {
+                    return None;
+                }
+                let (matcher, _) = Gitignore::new(&file);
+                (!matcher.is_empty()).then(|| Arc::new(matcher))
+            })
+            .as_deref()
+    }
+}
@@ -1,10 +1,37 @@
 #![allow(nonstandard_style)]
+mod cursor;
+mod db;
+mod fuzzy;
+mod ignore_rules;
+mod metadata;
+mod migrations;
+mod query;
+mod recovery;
+mod roots;
+mod scan_job;
+mod scanner;
+mod search_index;
+mod search_key;
+mod sort;
+mod tags;
+mod watcher;
+
+use std::path::Path;
+
 use godot::prelude::*;
-use godot::classes::ProjectSettings;
-use rusqlite::{Connection, params, Result as SqlResult};
+use godot::classes::{Engine, ProjectSettings, SceneTree};
+use rusqlite::{params, params_from_iter, Result as SqlResult};
+use rusqlite::types::Value;
 use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
-use regex::Regex;
+
+use cursor::Cursor;
+use ignore_rules::IgnoreRules;
+use query::{Query, SearchError, SearchTarget};
+use scan_job::{ScanEvent, ScanJob, ScanKind};
+use scanner::{CancelToken, Decision, PreviewRule, ScanIssue, ScanOptions, ScanSummary};
+use search_key::{natural_key, search_key, split_tokens};
+use sort::{SortKey, SortOrder, SortTerm};
+use watcher::{LibraryWatcher, WatchEvent};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct AssetData {
@@ -16,19 +43,54 @@ struct AssetData {
     image_path: Option<String>,
     #[serde(default)]
     tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
+    #[serde(default, skip)]
+    root_id: Option<i64>,
+    /// Discovery rule that produced a pack: asset_json, preview_pattern, folder_name, first_image or manual.
+    #[serde(default, skip)]
+    discovered_by: Option<String>,
+    /// The preview file name pattern that matched, for preview_pattern.
+    #[serde(default, skip)]
+    discovered_pattern: Option<String>,
 }
 
+/// Columns read by `row_to_asset`, tags as a JSON array. Individual assets have no discovery rule.
+const ASSET_COLUMNS: &str = "id, name, path, image_path, \
+    (SELECT json_group_array(g.label ORDER BY l.position) FROM asset_tags l JOIN tags g ON g.id = l.tag_id WHERE l.asset_id = assets.id), \
+    root_id, discovered_by, discovered_pattern, license";
+const INDIVIDUAL_ASSET_COLUMNS: &str = "id, name, path, image_path, \
+    (SELECT json_group_array(g.label ORDER BY l.position) FROM individual_asset_tags l JOIN tags g ON g.id = l.tag_id WHERE l.asset_id = individual_assets.id), \
+    root_id, NULL, NULL, NULL";
+
+/// Number of columns in `ASSET_COLUMNS` and `INDIVIDUAL_ASSET_COLUMNS`. Sort values for cursors follow them.
+const ASSET_COLUMN_COUNT: usize = 9;
+
+/// Search results, each with its score if they were matched fuzzily.
+type ScoredAssets = Vec<(AssetData, Option<f64>)>;
+
+/// Where a fuzzy result sorts: best score first, then by name key, name and id.
+type FuzzyKey = (f64, String, String, i64);
+
 #[derive(GodotClass)]
 #[class(base=RefCounted)]
 struct AssetManager {
     db_path: String,
+    pool: db::Pool,
     page_size: i64,
     last_error: godot::global::Error,
     preview_file_names: Vec<String>,
     use_first_image: bool,
     use_folder_name: bool,
+    scan_job: Option<ScanJob>,
+    watcher: Option<LibraryWatcher>,
+    root_filter: Option<i64>,
+    ignore_patterns: Vec<String>,
+    parallel_scan: bool,
+    sort_by_relevance: bool,
+    fuzzy_search: bool,
+    sort_order: SortOrder,
 
-    #[allow(dead_code)]
     base: Base<RefCounted>,
 }
 
@@ -37,11 +99,20 @@ impl IRefCounted for AssetManager {
     fn init(base: Base<RefCounted>) -> Self {
         Self {
             db_path: String::new(),
+            pool: db::Pool::new(""),
             page_size: 50,
             last_error: godot::global::Error::OK,
             preview_file_names: vec!["Preview".to_string(), "Asset".to_string()],
             use_first_image: false,
             use_folder_name: true,
+            scan_job: None,
+            watcher: None,
+            root_filter: None,
+            ignore_patterns: Vec::new(),
+            parallel_scan: false,
+            sort_by_relevance: true,
+            fuzzy_search: false,
+            sort_order: SortOrder::default(),
             base,
         }
     }
@@ -57,6 +128,11 @@ impl AssetManager {
     /// Initialize a new AssetManager with a SQLite database.
     ///
     /// The database will be created if it doesn't exist. Creates tables for assets and deleted paths.
+    /// [br]
+    /// If it can't be opened, [method get_error] returns [code]ERR_FILE_CORRUPT[/code] for a damaged file, which
+    /// [method recover_database] can rebuild, [code]ERR_FILE_UNRECOGNIZED[/code] for one written by a newer
+    /// version of the plugin, [code]ERR_BUSY[/code] if another process holds it, and [code]ERR_CANT_CREATE[/code]
+    /// otherwise.
     #[func]
     fn new_db(db_path: GString) -> Gd<Self> {
         // Convert Godot path (user://, res://, etc.) to real filesystem path
@@ -69,23 +145,26 @@ impl AssetManager {
             db_path.to_string()
         };
 
-        let mut instance = Gd::from_init_fn(|base| {
-            Self {
-                db_path: real_path,
-                page_size: 50,
-                last_error: godot::global::Error::OK,
-                preview_file_names: vec!["Preview".to_string(), "Asset".to_string()],
-                use_first_image: false,
-                use_folder_name: true,
-                base,
-            }
+        // Same defaults as `init`; only the database differs.
+        let mut instance = Gd::from_init_fn(|base| Self {
+            pool: db::Pool::new(real_path.as_str()),
+            db_path: real_path,
+            ..<Self as IRefCounted>::init(base)
         });
 
         // Initialize database
         let init_result = instance.bind_mut().init_database();
         if let Err(e) = init_result {
             godot_error!("Failed to initialize database: {}", e);
-            instance.bind_mut().last_error = godot::global::Error::ERR_CANT_CREATE;
+            instance.bind_mut().last_error = if recovery::is_corrupt(&e) {
+                godot::global::Error::ERR_FILE_CORRUPT
+            } else if migrations::is_newer_schema(&e) {
+                godot::global::Error::ERR_FILE_UNRECOGNIZED
+            } else if recovery::is_busy(&e) {
+                godot::global::Error::ERR_BUSY
+            } else {
+                godot::global::Error::ERR_CANT_CREATE
+            };
         }
 
         instance
@@ -146,33 +225,131 @@ impl AssetManager {
         self.use_folder_name = use_folder;
     }
 
-    /// Get the total number of pages based on current page size.
-    /// [b]Returns:[/b] [int] Total number of pages
+    /// Set patterns that every scan skips, in addition to [code].assetignore[/code] files.
+    ///
+    /// Both use .gitignore syntax: [code]node_modules/[/code] skips those folders anywhere, [code]*.blend1[/code]
+    /// skips matching files, a leading [code]/[/code] anchors a pattern to the scanned directory and
+    /// [code]![/code] re-includes a path. A [code].assetignore[/code] file applies to its own folder and
+    /// everything below it, and takes precedence over these patterns.
+    /// [br]
+    /// [param patterns]: [PackedStringArray] Ignore patterns, e.g. [code][".git/", "Source/", "*.blend1"][/code].
     #[func]
-    fn get_pages(&self) -> i64 {
-        match self.get_connection() {
-            Ok(conn) => {
-                let count: i64 = conn
-                    .query_row("SELECT COUNT(*) FROM assets", [], |row| row.get(0))
-                    .unwrap_or(0);
+    fn set_ignore_patterns(&mut self, patterns: PackedStringArray) {
+        self.ignore_patterns = patterns.as_slice().iter().map(|s| s.to_string()).collect();
+    }
+
+    /// Get the patterns set with [method set_ignore_patterns].
+    #[func]
+    fn get_ignore_patterns(&self) -> PackedStringArray {
+        self.ignore_patterns.iter().map(|s| GString::from(s.as_str())).collect()
+    }
+
+    /// Set whether pack scans look at folders in parallel.
+    ///
+    /// Folders are read and matched against the preview rules on a thread pool, while a single thread
+    /// writes the results to the database. Finds the same assets as the sequential scan, but is much
+    /// faster on a fast disk with a large library. Individual asset scans are not affected.
+    #[func]
+    fn set_parallel_scan(&mut self, parallel: bool) {
+        self.parallel_scan = parallel;
+    }
+
+    /// Set whether search results are ordered by relevance or by name.
+    ///
+    /// Relevance ranks a match in the name above one in the tags, and both above one in the path.
+    /// Queries with only [code]tag:[/code]/[code]root:[/code] filters are always ordered by name.
+    #[func]
+    fn set_sort_by_relevance(&mut self, by_relevance: bool) {
+        self.sort_by_relevance = by_relevance;
+    }
+
+    /// Get whether search results are ordered by relevance. See [method set_sort_by_relevance].
+    #[func]
+    fn get_sort_by_relevance(&self) -> bool {
+        self.sort_by_relevance
+    }
+
+    /// Set whether searches always match names and tags fuzzily, tolerating typos.
+    ///
+    /// When off, a search falls back to fuzzy matching only when it finds nothing. Fuzzy results are
+    /// ordered by how close they are, and each has a [code]score[/code] from 0 to 1.
+    #[func]
+    fn set_fuzzy_search(&mut self, fuzzy: bool) {
+        self.fuzzy_search = fuzzy;
+    }
+
+    /// Get whether searches are always fuzzy. See [method set_fuzzy_search].
+    #[func]
+    fn get_fuzzy_search(&self) -> bool {
+        self.fuzzy_search
+    }
+
+    /// Set the order [method get_assets], [method get_individual_assets] and the searches return assets in.
+    ///
+    /// [param order]: [String] One of [code]name[/code], [code]natural[/code] (numbers by value, so "Pack 2"
+    /// comes before "Pack 10"), [code]added[/code] (date added), [code]modified[/code] (last modified on
+    /// disk), [code]size[/code] (size on disk), [code]imported[/code] (see [method mark_imported]) or
+    /// [code]random[/code] (see [method set_sort_seed]). Sizes and modification times are recorded by scans
+    /// started while sorting by one of them; assets not scanned since come last.
+    /// [br][param descending]: [bool] Reverse the order. Assets without a date or size come last either way.
+    /// [br]Searches are still ordered by relevance (see [method set_sort_by_relevance]) for [code]name[/code]
+    /// ascending, and fuzzy results by how close they are.
+    /// [br][b]Returns:[/b] [constant ERR_INVALID_PARAMETER] for an unknown order, which is then left as it was.
+    #[func]
+    fn set_sort_order(&mut self, order: GString, descending: bool) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
 
-                (count + self.page_size - 1) / self.page_size
+        match SortKey::parse(&order.to_string()) {
+            Some(key) => {
+                self.sort_order.key = key;
+                self.sort_order.descending = descending;
+            }
+            None => {
+                godot_error!("Unknown sort order: {}", order);
+                self.last_error = godot::global::Error::ERR_INVALID_PARAMETER;
             }
-            Err(_) => 0,
         }
+        self.last_error
+    }
+
+    /// Get the order set with [method set_sort_order].
+    #[func]
+    fn get_sort_order(&self) -> GString {
+        GString::from(self.sort_order.key.name())
+    }
+
+    /// Get whether the order set with [method set_sort_order] is descending.
+    #[func]
+    fn get_sort_descending(&self) -> bool {
+        self.sort_order.descending
+    }
+
+    /// Set the seed of the [code]random[/code] order. The same seed gives the same order, so pages
+    /// don't repeat or skip assets.
+    #[func]
+    fn set_sort_seed(&mut self, seed: i64) {
+        self.sort_order.seed = seed;
+    }
+
+    /// Get whether pack scans run in parallel. See [method set_parallel_scan].
+    #[func]
+    fn get_parallel_scan(&self) -> bool {
+        self.parallel_scan
+    }
+
+    /// Get the total number of pages based on current page size.
+    /// [b]Returns:[/b] [int] Total number of pages
+    #[func]
+    fn get_pages(&self) -> i64 {
+        let count = self.count_rows("assets");
+        (count + self.page_size - 1) / self.page_size
     }
 
     /// Get the total number of assets in the database.
     /// [b]Returns:[/b] [int] Total count of all assets
     #[func]
     fn get_asset_count(&self) -> i64 {
-        match self.get_connection() {
-            Ok(conn) => {
-                conn.query_row("SELECT COUNT(*) FROM assets", [], |row| row.get(0))
-                    .unwrap_or(0)
-            }
-            Err(_) => 0,
-        }
+        self.count_rows("assets")
     }
 
     /// Get the last error that occurred.
@@ -182,216 +359,592 @@ impl AssetManager {
         self.last_error
     }
 
-    /// Scan a directory recursively to discover and add assets to the database.
+    /// Check the database file for damage with SQLite's integrity and foreign key checks.
+    ///
+    /// Reads the whole file, so it can take a while on a large library.
+    /// [br][b]Returns:[/b] [Dictionary] with [code]ok[/code], [code]damaged[/code], [code]errors[/code] (a
+    /// [PackedStringArray] of the problems found, such as damaged pages or indexes) and
+    /// [code]foreign_key_errors[/code] (an [Array] of [code]{table, rowid, parent}[/code] for rows referring to a
+    /// missing row). Only [code]errors[/code] make the file [code]damaged[/code], so that
+    /// [method recover_database] rebuilds it; a dangling reference doesn't. If the check can't run,
+    /// [code]ok[/code] is false, [code]errors[/code] holds the reason and [method get_error] returns
+    /// [code]ERR_FILE_CORRUPT[/code] (and [code]damaged[/code] is true) or [code]ERR_DATABASE_CANT_READ[/code].
     #[func]
-    fn find_assets(&mut self, path: GString) {
+    fn check_integrity(&mut self) -> VarDictionary {
         self.last_error = godot::global::Error::OK;
 
-        // Convert Godot path to real filesystem path
-        let real_path = if path.to_string().starts_with("user://") ||
-                          path.to_string().starts_with("res://") {
-            ProjectSettings::singleton()
-                .globalize_path(&path)
-                .to_string()
-        } else {
-            path.to_string()
+        let report = match self.get_connection().and_then(|conn| recovery::check_integrity(&conn)) {
+            Ok(report) => report,
+            Err(e) => {
+                godot_error!("Failed to check database: {}", e);
+                self.last_error = if recovery::is_corrupt(&e) {
+                    godot::global::Error::ERR_FILE_CORRUPT
+                } else {
+                    godot::global::Error::ERR_DATABASE_CANT_READ
+                };
+                recovery::IntegrityReport {
+                    errors: vec![e.to_string()],
+                    foreign_key_errors: Vec::new(),
+                }
+            }
         };
 
-        if let Err(e) = self.scan_directory(real_path) {
-            godot_error!("Error finding assets: {}", e);
-            self.last_error = godot::global::Error::ERR_FILE_CANT_READ;
+        let mut foreign_key_errors = VarArray::new();
+        for error in &report.foreign_key_errors {
+            let mut dict = VarDictionary::new();
+            dict.set("table", error.table.as_str());
+            dict.set("rowid", error.rowid.unwrap_or(-1));
+            dict.set("parent", error.parent.as_str());
+            foreign_key_errors.push(&dict.to_variant());
         }
+
+        let errors: PackedStringArray = report.errors.iter().map(|e| GString::from(e.as_str())).collect();
+        let mut dict = VarDictionary::new();
+        dict.set("ok", report.is_ok());
+        dict.set(
+            "damaged",
+            report.is_damaged() && self.last_error != godot::global::Error::ERR_DATABASE_CANT_READ,
+        );
+        dict.set("errors", &errors);
+        dict.set("foreign_key_errors", &foreign_key_errors);
+        dict
     }
 
-    /// Add a new asset to the database manually.
+    /// Rebuild a damaged database.
+    ///
+    /// The file is checked for damage like [method check_integrity] first (dangling references don't count), and
+    /// only rebuilt if it is damaged or [param force] is true. It is then moved aside as
+    /// [code]<database file>.corrupt-<unix time>[/code] and a new one is created. The library roots, the deleted
+    /// list and tag aliases and parents are carried over as far as they can still be read from the old file, then
+    /// every enabled root is scanned again in the background like [method scan_all_roots], which reads every
+    /// Asset.json back in. Edits made with [method update_asset] that aren't in an Asset.json are lost, unless
+    /// they were saved with [method export_metadata] and are imported again.
+    /// [br]
+    /// The scan emits the usual signals; [signal scan_finished] has [code]kind[/code] [code]"recovery"[/code] and
+    /// also [code]assets_recovered[/code], [code]from_asset_json[/code] and [code]individual_assets_recovered[/code].
+    /// If it's cancelled with [method cancel_scan], the roots stay and can be scanned again later.
+    /// Can't run while a scan or the watcher is running.
+    /// [br]
+    /// [param extensions]: [PackedStringArray] File extensions (without dot) for individual assets.
+    /// If empty, only packs are scanned.
+    /// [br][param force]: [bool] Rebuild even if the check finds no damage.
+    /// [br][b]Returns:[/b] [Dictionary] with [code]backup_path[/code] (empty if there was no file),
+    /// [code]previous_asset_count[/code] and [code]previous_individual_asset_count[/code] (-1 if the old file
+    /// couldn't be counted), [code]roots_restored[/code], [code]deleted_restored[/code],
+    /// [code]tag_links_restored[/code] and [code]salvage_errors[/code] (what couldn't be read from the old file).
+    /// Empty on error (see [method get_error]): [code]ERR_ALREADY_EXISTS[/code] if the database isn't damaged and
+    /// [param force] is false, [code]ERR_BUSY[/code] if it is in use. If only the scan couldn't start,
+    /// the dictionary is filled in and the error is [code]ERR_CANT_CREATE[/code].
     #[func]
-    fn add_asset(&mut self, name: GString, path: GString, image_path: GString, tags: Array<GString>) -> i64 {
+    fn recover_database(&mut self, extensions: PackedStringArray, force: bool) -> VarDictionary {
         self.last_error = godot::global::Error::OK;
 
-        let tags_vec: Vec<String> = tags.iter_shared().map(|s| s.to_string()).collect();
-        let img_path = if image_path.is_empty() {
-            None
-        } else {
-            Some(image_path.to_string())
+        if self.scan_job.is_some() || self.watcher.is_some() {
+            self.last_error = godot::global::Error::ERR_BUSY;
+            return VarDictionary::new();
+        }
+
+        self.pool.close_idle();
+        if !force {
+            // Moving a healthy file aside would lose every edit that isn't in an Asset.json.
+            match recovery::is_damaged(&self.db_path) {
+                Ok(true) => {}
+                Ok(false) => {
+                    godot_error!("The database isn't damaged; pass force to rebuild it anyway");
+                    self.last_error = godot::global::Error::ERR_ALREADY_EXISTS;
+                    return VarDictionary::new();
+                }
+                Err(e) => {
+                    godot_error!("Failed to check database before rebuilding it: {}", e);
+                    self.last_error = if recovery::is_busy(&e) {
+                        godot::global::Error::ERR_BUSY
+                    } else {
+                        godot::global::Error::ERR_DATABASE_CANT_READ
+                    };
+                    return VarDictionary::new();
+                }
+            }
+        }
+        let salvage = recovery::salvage(&self.db_path);
+        let backup = match recovery::back_up(&self.db_path) {
+            Ok(backup) => backup,
+            Err(e) => {
+                godot_error!("Failed to move the damaged database aside: {}", e);
+                self.last_error = godot::global::Error::ERR_FILE_CANT_WRITE;
+                return VarDictionary::new();
+            }
         };
 
-        match self.insert_asset(&name.to_string(), &path.to_string(), img_path.as_deref(), &tags_vec) {
-            Ok(id) => id,
+        let result = self.init_database().and_then(|()| {
+            let mut conn = self.get_connection()?;
+            recovery::restore(&mut conn, &salvage)
+        });
+
+        let recovered = match result {
+            Ok(recovered) => recovered,
             Err(e) => {
-                godot_error!("Failed to add asset: {}", e);
-                self.last_error = godot::global::Error::ERR_CANT_CREATE;
-                -1
+                godot_error!("Failed to rebuild database: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+                return VarDictionary::new();
             }
-        }
+        };
+
+        let salvage_errors: PackedStringArray = salvage.errors.iter().map(|e| GString::from(e.as_str())).collect();
+        let mut dict = VarDictionary::new();
+        dict.set("backup_path", backup.as_deref().unwrap_or(""));
+        dict.set("previous_asset_count", salvage.asset_count.unwrap_or(-1));
+        dict.set("previous_individual_asset_count", salvage.individual_asset_count.unwrap_or(-1));
+        dict.set("roots_restored", recovered.roots_restored);
+        dict.set("deleted_restored", recovered.deleted_restored);
+        dict.set("tag_links_restored", recovered.tag_links_restored);
+        dict.set("salvage_errors", &salvage_errors);
+
+        // The rest is found on disk, which takes a while on a large library.
+        let exts: Vec<String> = extensions.as_slice().iter().map(|s| s.to_string()).collect();
+        self.start_scan_job(String::new(), ScanKind::Recovery(exts));
+        dict
     }
 
-    /// Get a single asset by its ID.
+    /// Scan a directory recursively to discover and add assets to the database.
+    ///
+    /// Runs on the calling thread. Use [method start_scan] to scan in the background.
+    /// [br]
+    /// [param path]: [String] Directory to scan.
+    /// [br][b]Returns:[/b] [Dictionary] Scan report: [code]dirs_visited[/code], [code]assets_found[/code] (added),
+    /// [code]updated[/code], [code]pruned[/code], [code]skipped_existing[/code], [code]skipped_deleted[/code],
+    /// [code]nested_removed[/code], [code]auto_filled[/code], [code]elapsed_ms[/code], [code]cancelled[/code], and
+    /// [code]parse_failures[/code], [code]io_errors[/code] and [code]write_errors[/code] as arrays of
+    /// [code]{path, error}[/code] dictionaries. Empty if the scan failed (see [method get_error]).
     #[func]
-    fn get_asset(&mut self, id: i64) -> VarDictionary {
+    fn find_assets(&mut self, path: GString) -> VarDictionary {
         self.last_error = godot::global::Error::OK;
 
-        match self.fetch_asset(id) {
-            Ok(Some(asset)) => self.asset_to_dict(&asset),
-            Ok(None) => {
-                self.last_error = godot::global::Error::ERR_DOES_NOT_EXIST;
-                let mut dict = VarDictionary::new();
-                dict.set("error", "Asset not found");
-                dict
-            }
+        let real_path = Self::globalize(&path);
+        let options = self.scan_options();
+
+        let result = self.get_connection().and_then(|mut conn| {
+            scanner::scan_directory(&mut conn, &real_path, &options, &CancelToken::default(), &mut |_| {})
+        });
+
+        match result {
+            Ok(summary) => Self::scan_report(&summary),
             Err(e) => {
-                godot_error!("Failed to get asset: {}", e);
-                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
-                let mut dict = VarDictionary::new();
-                dict.set("error", e.to_string());
-                dict
+                godot_error!("Error finding assets: {}", e);
+                self.last_error = godot::global::Error::ERR_FILE_CANT_READ;
+                VarDictionary::new()
             }
         }
     }
 
-    /// Get a page of assets from the database.
+    /// Dry run of [method find_assets]: explain what the discovery rules decide for each folder, without
+    /// writing anything.
+    ///
+    /// Useful for tuning [method set_preview_file_names], [method set_use_folder_name] and
+    /// [method set_use_first_image]. Folders that are already in the database are explained as if they
+    /// weren't, so the effect of new settings can be checked before rescanning.
+    /// [br]
+    /// [param path]: [String] Directory to explain.
+    /// [br][b]Returns:[/b] [Array] One [Dictionary] per visited folder with [code]path[/code],
+    /// [code]decision[/code] ([code]"asset_json"[/code], [code]"preview_pattern"[/code], [code]"folder_name"[/code],
+    /// [code]"first_image"[/code], [code]"none"[/code], [code]"deleted"[/code] or [code]"ignored"[/code]),
+    /// [code]pattern_index[/code] and [code]pattern[/code] (for [code]"preview_pattern"[/code], otherwise -1 and ""),
+    /// [code]matched_file[/code], [code]has_asset_json[/code], [code]is_asset[/code], [code]skipped_subdirs[/code]
+    /// and [code]error[/code].
     #[func]
-    fn get_assets(&mut self, page: i64) -> VarDictionary {
+    fn explain_scan(&mut self, path: GString) -> VarArray {
         self.last_error = godot::global::Error::OK;
 
-        let page = page.max(1);
-        let offset = (page - 1) * self.page_size;
-
-        match self.fetch_assets_page(offset, self.page_size) {
-            Ok(assets) => {
-                let mut dict = VarDictionary::new();
-                dict.set("page_number", page);
-                dict.set("page_size", self.page_size);
-                dict.set("num_of_pages", self.get_pages());
+        let real_path = Self::globalize(&path);
+        let options = self.scan_options();
 
-                let mut assets_array = VarArray::new();
-                for asset in &assets {
-                    assets_array.push(&self.asset_to_dict(asset).to_variant());
-                }
-                dict.set("assets", &assets_array);
-                dict
-            }
+        let explanations = match self.get_connection().and_then(|conn| scanner::explain_scan(&conn, &real_path, &options)) {
+            Ok(explanations) => explanations,
             Err(e) => {
-                godot_error!("Failed to get assets: {}", e);
-                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
-                VarDictionary::new()
+                godot_error!("Error explaining scan: {}", e);
+                self.last_error = godot::global::Error::ERR_FILE_CANT_READ;
+                return VarArray::new();
             }
+        };
+
+        let mut array = VarArray::new();
+        for explanation in &explanations {
+            let pattern_index = match explanation.decision {
+                Decision::Preview(PreviewRule::Pattern(index)) => Some(index),
+                _ => None,
+            };
+
+            let mut dict = VarDictionary::new();
+            dict.set("path", explanation.path.as_str());
+            dict.set("decision", explanation.decision.name());
+            dict.set("pattern_index", pattern_index.map_or(-1, |i| i as i64));
+            dict.set("pattern", pattern_index.map_or("", |i| options.preview_file_names[i].as_str()));
+            dict.set("matched_file", explanation.matched_file.as_deref().unwrap_or(""));
+            dict.set("has_asset_json", explanation.has_asset_json);
+            dict.set("is_asset", explanation.is_asset);
+            dict.set("skipped_subdirs", explanation.skipped_subdirs);
+            dict.set("error", explanation.error.as_deref().unwrap_or(""));
+            array.push(&dict.to_variant());
         }
+        array
     }
 
-    /// Update specific fields of an existing asset. the data dictionary is the same as the Asset.json file
+    /// Start scanning a directory for assets on a worker thread.
+    ///
+    /// The worker uses its own database connection and reports back through [signal scan_progress],
+    /// [signal scan_finished] and [signal scan_failed]. Only one scan can run at a time.
+    /// [br]
+    /// [param path]: [String] Directory to scan.
+    /// [br][b]Returns:[/b] [Error] [code]ERR_BUSY[/code] if a scan is already running, otherwise OK.
     #[func]
-    fn update_asset(&mut self, id: i64, data: VarDictionary) -> godot::global::Error {
-        self.last_error = godot::global::Error::OK;
+    fn start_scan(&mut self, path: GString) -> godot::global::Error {
+        self.start_scan_job(Self::globalize(&path), ScanKind::Packs)
+    }
 
-        if data.is_empty() {
-            self.last_error = godot::global::Error::ERR_INVALID_DATA;
-            return self.last_error;
+    /// Start scanning a directory for individual asset files on a worker thread.
+    ///
+    /// Same as [method find_individual_assets], but reports back through the scan signals
+    /// like [method start_scan].
+    /// [br]
+    /// [param path]: [String] Directory to scan.
+    /// [br][param extensions]: [PackedStringArray] File extensions (without dot) to include.
+    #[func]
+    fn start_individual_scan(&mut self, path: GString, extensions: PackedStringArray) -> godot::global::Error {
+        let exts: Vec<String> = extensions.as_slice().iter().map(|s| s.to_string()).collect();
+        self.start_scan_job(Self::globalize(&path), ScanKind::Individual(exts))
+    }
+
+    /// Stop the running background scan.
+    ///
+    /// The scan stops at the next directory entry and emits [signal scan_cancelled] instead of [signal scan_finished].
+    /// [br]
+    /// [param commit_partial]: [bool] If true, keep the assets found so far. Otherwise the whole scan is rolled back, including every root already scanned by [method scan_all_roots].
+    /// [br][b]Returns:[/b] [Error] [code]ERR_DOES_NOT_EXIST[/code] if no scan is running, otherwise OK.
+    #[func]
+    fn cancel_scan(&mut self, commit_partial: bool) -> godot::global::Error {
+        match &self.scan_job {
+            Some(job) => {
+                job.cancel(commit_partial);
+                godot::global::Error::OK
+            }
+            None => godot::global::Error::ERR_DOES_NOT_EXIST,
         }
+    }
 
-        let mut valid_keys = 0;
-        let mut name: Option<String> = None;
-        let mut path: Option<String> = None;
-        let mut image_path: Option<Option<String>> = None;
-        let mut tags: Option<Vec<String>> = None;
+    /// Whether a background scan started with [method start_scan] or [method start_individual_scan] is running.
+    #[func]
+    fn is_scanning(&self) -> bool {
+        self.scan_job.is_some()
+    }
 
-        for key in data.keys_array().iter_shared() {
-            let key_str = key.to_string();
-            match key_str.as_str() {
-                "name" => {
-                    if let Some(val) = data.get(&key) {
-                        name = Some(val.to_string());
-                        valid_keys += 1;
-                    }
+    /// Forward events from the background scan as signals. Connected to [signal SceneTree.process_frame]
+    /// while a scan is running, there is no need to call it manually.
+    #[func]
+    fn _poll_scan(&mut self) {
+        let events = match &self.scan_job {
+            Some(job) => job.poll(),
+            None => return,
+        };
+
+        for event in events {
+            match event {
+                ScanEvent::Progress(summary) => {
+                    self.signals().scan_progress().emit(summary.dirs_visited, summary.assets_found);
                 }
-                "path" => {
-                    if let Some(val) = data.get(&key) {
-                        path = Some(val.to_string());
-                        valid_keys += 1;
-                    }
+                ScanEvent::Finished(summary) => {
+                    let dict = self.finish_scan_job(&summary);
+                    self.signals().scan_finished().emit(&dict);
+                    return;
                 }
-                "image_path" => {
-                    if let Some(val) = data.get(&key) {
-                        let val_str = val.to_string();
-                        image_path = Some(if val_str.is_empty() { None } else { Some(val_str) });
-                        valid_keys += 1;
-                    }
+                ScanEvent::Cancelled(summary) => {
+                    let dict = self.finish_scan_job(&summary);
+                    self.signals().scan_cancelled().emit(&dict);
+                    return;
                 }
-                "tags" => {
-                    if let Some(val) = data.get(&key) {
-                        if let Ok(arr) = val.try_to::<Array<GString>>() {
-                            tags = Some(arr.iter_shared().map(|s| s.to_string()).collect());
-                            valid_keys += 1;
-                        }
-                    }
+                ScanEvent::Failed(message) => {
+                    godot_error!("Background scan failed: {}", message);
+                    self.finish_scan_job(&ScanSummary::default());
+                    self.last_error = godot::global::Error::ERR_FILE_CANT_READ;
+                    self.signals().scan_failed().emit(message.as_str());
+                    return;
                 }
-                _ => {}
             }
         }
+    }
 
-        if valid_keys == 0 {
-            self.last_error = godot::global::Error::ERR_INVALID_DATA;
-            return self.last_error;
-        }
+    /// Watch a library root and keep the database in sync with it.
+    ///
+    /// Created, removed and renamed folders and files are run through the same discovery rules as
+    /// [method find_assets] and, if [param extensions] is not empty, [method find_individual_assets].
+    /// [signal assets_changed] is emitted whenever that changed the database. Replaces any previous watch.
+    /// [br]
+    /// [param path]: [String] Directory to watch recursively.
+    /// [br][param extensions]: [PackedStringArray] File extensions (without dot) for individual assets.
+    #[func]
+    fn start_watching(&mut self, path: GString, extensions: PackedStringArray) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+        self.stop_watching();
 
-        match self.update_asset_fields(id, name.as_deref(), path.as_deref(), image_path.as_ref().and_then(|o| o.as_deref()), tags.as_deref()) {
-            Ok(_) => godot::global::Error::OK,
+        let real_path = Self::globalize(&path);
+        let exts: Vec<String> = extensions.as_slice().iter().map(|s| s.to_string()).collect();
+
+        match LibraryWatcher::spawn(self.db_path.clone(), real_path, self.scan_options(), exts) {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                self.set_polling("_poll_watcher", true);
+            }
             Err(e) => {
-                godot_error!("Failed to update asset: {}", e);
-                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
-                self.last_error
+                godot_error!("Failed to watch {}: {}", path, e);
+                self.last_error = godot::global::Error::ERR_CANT_OPEN;
             }
         }
+        self.last_error
     }
 
-    /// Delete an asset from the database.
-    /// [param id]: [int] The asset ID to delete
-    /// [br][param remember_deleted]: [bool] If true, marks the path as deleted to skip it in find_assets()
+    /// Stop the watch started with [method start_watching].
     #[func]
-    fn delete_asset(&mut self, id: i64, remember_deleted: bool) -> godot::global::Error {
-        self.last_error = godot::global::Error::OK;
+    fn stop_watching(&mut self) {
+        if self.watcher.take().is_some() {
+            self.set_polling("_poll_watcher", false);
+        }
+    }
 
-        let path = if remember_deleted {
-            match self.fetch_asset(id) {
-                Ok(Some(asset)) => Some(asset.path),
-                _ => None,
-            }
-        } else {
-            None
-        };
+    /// Whether [method start_watching] is active.
+    #[func]
+    fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
 
-        match self.remove_asset(id) {
-            Ok(_) => {
-                if let Some(p) = path {
-                    if let Err(e) = self.mark_deleted(&p) {
-                        godot_error!("Failed to mark as deleted: {}", e);
-                    }
+    /// Forward events from the library watcher as signals. Connected to [signal SceneTree.process_frame]
+    /// while watching, there is no need to call it manually.
+    #[func]
+    fn _poll_watcher(&mut self) {
+        let events = match &self.watcher {
+            Some(watcher) => watcher.poll(),
+            None => return,
+        };
+
+        let mut changed = false;
+        for event in events {
+            match event {
+                WatchEvent::Changed => changed = true,
+                WatchEvent::Failed(message) => {
+                    let root = self.watcher.as_ref().map(|w| w.root.clone()).unwrap_or_default();
+                    godot_error!("Error watching {}: {}", root, message);
                 }
-                godot::global::Error::OK
             }
+        }
+
+        if changed {
+            self.signals().assets_changed().emit();
+        }
+    }
+
+    /// Emitted when [method start_watching] picked up changes on disk and updated the database.
+    #[signal]
+    fn assets_changed();
+
+    /// Emitted periodically while a background scan is running.
+    #[signal]
+    fn scan_progress(dirs_visited: i64, assets_found: i64);
+
+    /// Emitted when a background scan completes. [param summary] holds [code]kind[/code], [code]path[/code],
+    /// [code]dirs_visited[/code], [code]assets_found[/code], [code]updated[/code], [code]pruned[/code],
+    /// [code]elapsed_ms[/code] and [code]cancelled[/code]. The scan [method recover_database] starts adds the
+    /// recovered counts.
+    #[signal]
+    fn scan_finished(summary: VarDictionary);
+
+    /// Emitted instead of [signal scan_finished] when a background scan was stopped with [method cancel_scan].
+    /// [param summary] has the same keys, with [code]cancelled[/code] set to true.
+    #[signal]
+    fn scan_cancelled(summary: VarDictionary);
+
+    /// Emitted when a background scan could not complete.
+    #[signal]
+    fn scan_failed(message: GString);
+
+    /// Register a library root. Assets found under it are linked to it, so they can be filtered by root
+    /// and are removed with it.
+    ///
+    /// Adding a path that already is a root keeps its id and only updates the label.
+    /// [br]
+    /// [param path]: [String] Directory of the library.
+    /// [br][param label]: [String] Name shown for the root and matched by [code]root:[/code] in searches.
+    /// [br][b]Returns:[/b] [int] Root id, or -1 on error.
+    #[func]
+    fn add_root(&mut self, path: GString, label: GString) -> i64 {
+        self.last_error = godot::global::Error::OK;
+
+        let real_path = Self::globalize(&path);
+        match self.get_connection().and_then(|conn| roots::add_root(&conn, &real_path, &label.to_string())) {
+            Ok(id) => id,
             Err(e) => {
-                godot_error!("Failed to delete asset: {}", e);
+                godot_error!("Failed to add root: {}", e);
                 self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
-                self.last_error
+                -1
             }
         }
     }
 
-    /// Search for assets matching a query string.
-    /// [param query]: [String] Search string to match against
-    /// [br][param page]: [int] Page number of results (minimum 1)
+    /// Remove a library root together with every asset and individual asset linked to it.
     #[func]
-    fn search(&mut self, query: GString, page: i64) -> VarDictionary {
+    fn remove_root(&mut self, root_id: i64) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        match self.get_connection().and_then(|mut conn| roots::remove_root(&mut conn, root_id)) {
+            Ok(true) => {
+                if self.root_filter == Some(root_id) {
+                    self.root_filter = None;
+                }
+            }
+            Ok(false) => self.last_error = godot::global::Error::ERR_DOES_NOT_EXIST,
+            Err(e) => {
+                godot_error!("Failed to remove root: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+            }
+        }
+        self.last_error
+    }
+
+    /// Enable or disable a library root. Disabled roots are skipped by [method scan_all_roots]
+    /// and their assets are hidden unless the root is selected with [method set_root_filter].
+    #[func]
+    fn set_root_enabled(&mut self, root_id: i64, enabled: bool) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        match self.get_connection().and_then(|conn| roots::set_root_enabled(&conn, root_id, enabled)) {
+            Ok(true) => {}
+            Ok(false) => self.last_error = godot::global::Error::ERR_DOES_NOT_EXIST,
+            Err(e) => {
+                godot_error!("Failed to update root: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+            }
+        }
+        self.last_error
+    }
+
+    /// Get every library root.
+    /// [b]Returns:[/b] [Array] of [Dictionary] with [code]id[/code], [code]path[/code], [code]label[/code],
+    /// [code]enabled[/code], [code]asset_count[/code] and [code]individual_asset_count[/code].
+    #[func]
+    fn list_roots(&mut self) -> VarArray {
+        self.last_error = godot::global::Error::OK;
+
+        let result = self.get_connection().and_then(|conn| {
+            let mut list = Vec::new();
+            for root in roots::list_roots(&conn)? {
+                let counts = roots::root_counts(&conn, root.id)?;
+                list.push((root, counts));
+            }
+            Ok(list)
+        });
+
+        let mut array = VarArray::new();
+        match result {
+            Ok(list) => {
+                for (root, (asset_count, individual_count)) in list {
+                    let mut dict = VarDictionary::new();
+                    dict.set("id", root.id);
+                    dict.set("path", root.path.as_str());
+                    dict.set("label", root.label.as_str());
+                    dict.set("enabled", root.enabled);
+                    dict.set("asset_count", asset_count);
+                    dict.set("individual_asset_count", individual_count);
+                    array.push(&dict.to_variant());
+                }
+            }
+            Err(e) => {
+                godot_error!("Failed to list roots: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+            }
+        }
+        array
+    }
+
+    /// Only show assets of one root in pages, counts and searches.
+    /// [param root_id]: [int] Root to show, or -1 to show every enabled root.
+    #[func]
+    fn set_root_filter(&mut self, root_id: i64) {
+        self.root_filter = if root_id < 0 { None } else { Some(root_id) };
+    }
+
+    /// Get the root selected with [method set_root_filter], or -1.
+    #[func]
+    fn get_root_filter(&self) -> i64 {
+        self.root_filter.unwrap_or(-1)
+    }
+
+    /// Scan every enabled root on a worker thread, like [method start_scan].
+    /// [br]
+    /// [param extensions]: [PackedStringArray] File extensions (without dot) for individual assets.
+    /// If empty, only packs are scanned.
+    /// [br]
+    /// All roots are written in one transaction, so [method cancel_scan] keeps or rolls back all of them together.
+    #[func]
+    fn scan_all_roots(&mut self, extensions: PackedStringArray) -> godot::global::Error {
+        let exts: Vec<String> = extensions.as_slice().iter().map(|s| s.to_string()).collect();
+        self.start_scan_job(String::new(), ScanKind::AllRoots(exts))
+    }
+
+    /// Add a new asset to the database manually.
+    #[func]
+    fn add_asset(&mut self, name: GString, path: GString, image_path: GString, tags: Array<GString>) -> i64 {
+        self.last_error = godot::global::Error::OK;
+
+        let tags_vec: Vec<String> = tags.iter_shared().map(|s| s.to_string()).collect();
+        let img_path = if image_path.is_empty() {
+            None
+        } else {
+            Some(image_path.to_string())
+        };
+
+        match self.insert_asset(&name.to_string(), &path.to_string(), img_path.as_deref(), &tags_vec) {
+            Ok(id) => id,
+            Err(e) => {
+                godot_error!("Failed to add asset: {}", e);
+                self.last_error = godot::global::Error::ERR_CANT_CREATE;
+                -1
+            }
+        }
+    }
+
+    /// Get a single asset by its ID.
+    #[func]
+    fn get_asset(&mut self, id: i64) -> VarDictionary {
+        self.last_error = godot::global::Error::OK;
+
+        match self.fetch_asset(id) {
+            Ok(Some(asset)) => self.asset_to_dict(&asset),
+            Ok(None) => {
+                self.last_error = godot::global::Error::ERR_DOES_NOT_EXIST;
+                let mut dict = VarDictionary::new();
+                dict.set("error", "Asset not found");
+                dict
+            }
+            Err(e) => {
+                godot_error!("Failed to get asset: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+                let mut dict = VarDictionary::new();
+                dict.set("error", e.to_string());
+                dict
+            }
+        }
+    }
+
+    /// Get a page of assets from the database.
+    #[func]
+    fn get_assets(&mut self, page: i64) -> VarDictionary {
         self.last_error = godot::global::Error::OK;
 
         let page = page.max(1);
         let offset = (page - 1) * self.page_size;
 
-        match self.search_assets(&query.to_string(), offset, self.page_size) {
-            Ok((assets, total_count)) => {
+        match self.fetch_assets_page(offset, self.page_size) {
+            Ok(assets) => {
                 let mut dict = VarDictionary::new();
                 dict.set("page_number", page);
                 dict.set("page_size", self.page_size);
-                dict.set("num_of_pages", (total_count + self.page_size - 1) / self.page_size);
+                dict.set("num_of_pages", self.get_pages());
 
                 let mut assets_array = VarArray::new();
                 for asset in &assets {
@@ -401,41 +954,228 @@ impl AssetManager {
                 dict
             }
             Err(e) => {
-                godot_error!("Failed to search assets: {}", e);
+                godot_error!("Failed to get assets: {}", e);
                 self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
                 VarDictionary::new()
             }
         }
     }
+
+    /// Get the assets after [param cursor], for browsing a long list without page numbers.
+    ///
+    /// Each batch starts right after the last asset of the previous one, so assets added or removed
+    /// meanwhile (by a scan, say) don't make later batches repeat or skip any, and deep batches are as
+    /// fast as the first. Assets are in the order set with [method set_sort_order].
+    /// [br]
+    /// [param cursor]: [String] [code]next_cursor[/code] of the previous batch, or empty for the first.
+    /// [br][param limit]: [int] Most assets to return (minimum 1).
+    /// [br][b]Returns:[/b] [Dictionary] with [code]assets[/code] and [code]next_cursor[/code], which is empty
+    /// after the last batch. A cursor from another order or root filter (see [method set_root_filter]), or
+    /// from a search, gives [constant ERR_INVALID_PARAMETER] and an [code]error[/code].
+    #[func]
+    fn get_assets_after(&mut self, cursor: GString, limit: i64) -> VarDictionary {
+        self.batch_after(&query::PACKS, None, &cursor.to_string(), limit)
+    }
+
+    /// Update specific fields of an existing asset. the data dictionary is the same as the Asset.json file
+    #[func]
+    fn update_asset(&mut self, id: i64, data: VarDictionary) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        if data.is_empty() {
+            self.last_error = godot::global::Error::ERR_INVALID_DATA;
+            return self.last_error;
+        }
+
+        let mut valid_keys = 0;
+        let mut name: Option<String> = None;
+        let mut path: Option<String> = None;
+        let mut image_path: Option<Option<String>> = None;
+        let mut tags: Option<Vec<String>> = None;
+
+        for key in data.keys_array().iter_shared() {
+            let key_str = key.to_string();
+            match key_str.as_str() {
+                "name" => {
+                    if let Some(val) = data.get(&key) {
+                        name = Some(val.to_string());
+                        valid_keys += 1;
+                    }
+                }
+                "path" => {
+                    if let Some(val) = data.get(&key) {
+                        path = Some(val.to_string());
+                        valid_keys += 1;
+                    }
+                }
+                "image_path" => {
+                    if let Some(val) = data.get(&key) {
+                        let val_str = val.to_string();
+                        image_path = Some(if val_str.is_empty() { None } else { Some(val_str) });
+                        valid_keys += 1;
+                    }
+                }
+                "tags" => {
+                    if let Some(val) = data.get(&key) {
+                        if let Ok(arr) = val.try_to::<Array<GString>>() {
+                            tags = Some(arr.iter_shared().map(|s| s.to_string()).collect());
+                            valid_keys += 1;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if valid_keys == 0 {
+            self.last_error = godot::global::Error::ERR_INVALID_DATA;
+            return self.last_error;
+        }
+
+        match self.update_asset_fields(id, name.as_deref(), path.as_deref(), image_path.as_ref().and_then(|o| o.as_deref()), tags.as_deref()) {
+            Ok(_) => godot::global::Error::OK,
+            Err(e) => {
+                godot_error!("Failed to update asset: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+                self.last_error
+            }
+        }
+    }
+
+    /// Delete an asset from the database.
+    /// [param id]: [int] The asset ID to delete
+    /// [br][param remember_deleted]: [bool] If true, marks the path as deleted to skip it in find_assets()
+    #[func]
+    fn delete_asset(&mut self, id: i64, remember_deleted: bool) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        let path = if remember_deleted {
+            match self.fetch_asset(id) {
+                Ok(Some(asset)) => Some(asset.path),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        match self.remove_asset(id) {
+            Ok(_) => {
+                if let Some(p) = path {
+                    if let Err(e) = self.mark_deleted(&p) {
+                        godot_error!("Failed to mark as deleted: {}", e);
+                    }
+                }
+                godot::global::Error::OK
+            }
+            Err(e) => {
+                godot_error!("Failed to delete asset: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+                self.last_error
+            }
+        }
+    }
+
+    /// Record that an asset was just imported into the project, for the [code]imported[/code] order of
+    /// [method set_sort_order].
+    /// [br][b]Returns:[/b] [constant ERR_DOES_NOT_EXIST] if there is no asset with [param id].
+    #[func]
+    fn mark_imported(&mut self, id: i64) -> godot::global::Error {
+        self.mark_imported_in("assets", id)
+    }
+
+    /// Search for assets matching a query string.
+    ///
+    /// Plain terms are looked up in a full-text index of names, tags and path segments. Each term
+    /// matches words starting with it, and results are ordered by relevance (see [method set_sort_by_relevance]).
+    /// [br]Terms are combined with AND unless joined by [code]OR[/code], and can be grouped with parentheses.
+    /// [code]-term[/code] excludes matches, [code]"two words"[/code] matches a phrase.
+    /// [code]name:[/code], [code]path:[/code], [code]ext:[/code], [code]root:[/code], [code]kind:[/code],
+    /// [code]license:[/code], [code]tag:[/code], [code]discovered:[/code] and [code]pattern:[/code] match one field.
+    /// [br]
+    /// [param query]: [String] Search string to match against
+    /// [br][param page]: [int] Page number of results (minimum 1)
+    /// [br][b]Returns:[/b] [Dictionary] with page_number, page_size, num_of_pages and assets. If the query
+    /// doesn't parse, [method get_error] is [constant ERR_PARSE_ERROR] and [code]error[/code] and
+    /// [code]error_position[/code] describe the problem.
+    #[func]
+    fn search(&mut self, query: GString, page: i64) -> VarDictionary {
+        self.search_page(&query::PACKS, &query.to_string(), page)
+    }
+
+    /// Search for assets like [method search], a batch at a time like [method get_assets_after].
+    ///
+    /// A batch continues the same ordering as the one before it, exact or fuzzy; pass the same
+    /// [param query] with each cursor. A cursor from another query gives [constant ERR_INVALID_PARAMETER].
+    /// [br][b]Returns:[/b] [Dictionary] with [code]assets[/code], [code]next_cursor[/code] and
+    /// [code]fuzzy[/code], or [code]error[/code] as for [method search].
+    #[func]
+    fn search_after(&mut self, query: GString, cursor: GString, limit: i64) -> VarDictionary {
+        self.batch_after(&query::PACKS, Some(&query.to_string()), &cursor.to_string(), limit)
+    }
+
     /// Scan a directory recursively to discover and add individual asset files to the database.
     ///
-    /// [param path]: [String] Directory to scan.
-    /// [br][param extensions]: [PackedStringArray] File extensions (without dot) to include, e.g. ["png", "obj"].
-    /// macOS resource-fork folders ([code]__MACOSX[/code]) are ignored.
+    /// [param path]: [String] Directory to scan.
+    /// [br][param extensions]: [PackedStringArray] File extensions (without dot) to include, e.g. ["png", "obj"].
+    /// macOS resource-fork folders ([code]__MACOSX[/code]) are ignored, as is anything excluded by
+    /// [code].assetignore[/code] files or [method set_ignore_patterns].
+    /// [br][b]Returns:[/b] [Dictionary] Scan report, same keys as [method find_assets].
+    #[func]
+    fn find_individual_assets(&mut self, path: GString, extensions: PackedStringArray) -> VarDictionary {
+        self.last_error = godot::global::Error::OK;
+
+        let real_path = Self::globalize(&path);
+        let exts: Vec<String> = extensions.as_slice().iter().map(|s| s.to_string()).collect();
+        let options = self.scan_options();
+
+        let result = self.get_connection().and_then(|mut conn| {
+            scanner::scan_individual_directory(&mut conn, &real_path, &exts, &options, &CancelToken::default(), &mut |_| {})
+        });
+
+        match result {
+            Ok(summary) => Self::scan_report(&summary),
+            Err(e) => {
+                godot_error!("Error finding individual assets: {}", e);
+                self.last_error = godot::global::Error::ERR_FILE_CANT_READ;
+                VarDictionary::new()
+            }
+        }
+    }
+
+    /// Remove assets and individual assets under a directory whose folders or files no longer exist,
+    /// or that are now excluded by [code].assetignore[/code] files or [method set_ignore_patterns].
+    ///
+    /// Scans already do this for the table they fill; this cleans up both without walking the tree.
+    /// [br]
+    /// [param path]: [String] Library root the rows must live under.
+    /// [br][b]Returns:[/b] [Dictionary] [code]assets[/code] and [code]individual_assets[/code] pruned counts.
     #[func]
-    fn find_individual_assets(&mut self, path: GString, extensions: PackedStringArray) {
+    fn prune_missing(&mut self, path: GString) -> VarDictionary {
         self.last_error = godot::global::Error::OK;
 
-        let real_path = if path.to_string().starts_with("user://") ||
-                          path.to_string().starts_with("res://") {
-            ProjectSettings::singleton()
-                .globalize_path(&path)
-                .to_string()
-        } else {
-            path.to_string()
-        };
+        let real_path = Self::globalize(&path);
+        let mut rules = IgnoreRules::new(Path::new(&real_path), &self.ignore_patterns);
+        let result = self.get_connection().and_then(|mut conn| {
+            let tx = conn.transaction()?;
+            let assets = scanner::prune_missing(&tx, "assets", &real_path, &mut rules)?;
+            let individual = scanner::prune_missing(&tx, "individual_assets", &real_path, &mut rules)?;
+            tx.commit()?;
+            Ok((assets, individual))
+        });
 
-        let mut exts: Vec<String> = Vec::new();
-        for i in 0..extensions.len() {
-            if let Some(s) = extensions.get(i) {
-                exts.push(s.to_string());
+        let mut dict = VarDictionary::new();
+        match result {
+            Ok((assets, individual)) => {
+                dict.set("assets", assets);
+                dict.set("individual_assets", individual);
+            }
+            Err(e) => {
+                godot_error!("Failed to prune missing assets: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+                dict.set("error", e.to_string());
             }
         }
-
-        if let Err(e) = self.scan_individual_directory(real_path, exts) {
-            godot_error!("Error finding individual assets: {}", e);
-            self.last_error = godot::global::Error::ERR_FILE_CANT_READ;
-        }
+        dict
     }
 
     /// Get a single individual asset by its ID.
@@ -491,61 +1231,41 @@ impl AssetManager {
         }
     }
 
+    /// Get the individual assets after [param cursor]. See [method get_assets_after].
+    #[func]
+    fn get_individual_assets_after(&mut self, cursor: GString, limit: i64) -> VarDictionary {
+        self.batch_after(&query::INDIVIDUAL, None, &cursor.to_string(), limit)
+    }
+
     /// Get the total number of individual assets in the database.
     #[func]
     fn get_individual_asset_count(&self) -> i64 {
-        match self.get_connection() {
-            Ok(conn) => {
-                conn.query_row("SELECT COUNT(*) FROM individual_assets", [], |row| row.get(0))
-                    .unwrap_or(0)
-            }
-            Err(_) => 0,
-        }
+        self.count_rows("individual_assets")
     }
 
     /// Get the total number of individual asset pages based on current page size.
     #[func]
     fn get_individual_asset_pages(&self) -> i64 {
-        match self.get_connection() {
-            Ok(conn) => {
-                let count: i64 = conn
-                    .query_row("SELECT COUNT(*) FROM individual_assets", [], |row| row.get(0))
-                    .unwrap_or(0);
-
-                (count + self.page_size - 1) / self.page_size
-            }
-            Err(_) => 0,
-        }
+        let count = self.count_rows("individual_assets");
+        (count + self.page_size - 1) / self.page_size
     }
 
-    /// Search for individual assets matching a query string.
+    /// Search for individual assets matching a query string, with the same syntax as [method search].
     #[func]
     fn search_individual_assets(&mut self, query: GString, page: i64) -> VarDictionary {
-        self.last_error = godot::global::Error::OK;
-
-        let page = page.max(1);
-        let offset = (page - 1) * self.page_size;
+        self.search_page(&query::INDIVIDUAL, &query.to_string(), page)
+    }
 
-        match self.search_individual(&query.to_string(), offset, self.page_size) {
-            Ok((assets, total_count)) => {
-                let mut dict = VarDictionary::new();
-                dict.set("page_number", page);
-                dict.set("page_size", self.page_size);
-                dict.set("num_of_pages", (total_count + self.page_size - 1) / self.page_size);
+    /// Record that an individual asset was just imported into the project. See [method mark_imported].
+    #[func]
+    fn mark_individual_imported(&mut self, id: i64) -> godot::global::Error {
+        self.mark_imported_in("individual_assets", id)
+    }
 
-                let mut assets_array = VarArray::new();
-                for asset in &assets {
-                    assets_array.push(&self.asset_to_dict(asset).to_variant());
-                }
-                dict.set("assets", &assets_array);
-                dict
-            }
-            Err(e) => {
-                godot_error!("Failed to search individual assets: {}", e);
-                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
-                VarDictionary::new()
-            }
-        }
+    /// Search for individual assets a batch at a time. See [method search_after].
+    #[func]
+    fn search_individual_assets_after(&mut self, query: GString, cursor: GString, limit: i64) -> VarDictionary {
+        self.batch_after(&query::INDIVIDUAL, Some(&query.to_string()), &cursor.to_string(), limit)
     }
 
     /// Delete an individual asset from the database.
@@ -581,117 +1301,490 @@ impl AssetManager {
         }
     }
 
+    /// Get every tag used by packs or by individual assets, most used first.
+    /// [br]
+    /// [param kind]: [String] [code]"packs"[/code] or [code]"individual"[/code].
+    /// [br][b]Returns:[/b] [Array] of [Dictionary] with [code]name[/code] and [code]count[/code], the number
+    /// of assets with the tag. Only assets shown by [method set_root_filter] are counted.
+    #[func]
+    fn get_all_tags(&mut self, kind: GString) -> VarArray {
+        self.last_error = godot::global::Error::OK;
+
+        let table = match kind.to_string().as_str() {
+            "packs" => "assets",
+            "individual" => "individual_assets",
+            other => {
+                godot_error!("Unknown asset kind '{}', expected \"packs\" or \"individual\"", other);
+                self.last_error = godot::global::Error::ERR_INVALID_PARAMETER;
+                return VarArray::new();
+            }
+        };
+
+        let mut array = VarArray::new();
+        match self.get_connection().and_then(|conn| tags::tag_counts(&conn, table, &self.scope_clause())) {
+            Ok(counts) => {
+                for (name, count) in counts {
+                    let mut dict = VarDictionary::new();
+                    dict.set("name", name.as_str());
+                    dict.set("count", count);
+                    array.push(&dict.to_variant());
+                }
+            }
+            Err(e) => {
+                godot_error!("Failed to list tags: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+            }
+        }
+        array
+    }
+
+    /// Suggest tags for autocomplete: tags starting with [param prefix], ignoring case and accents, most used first.
+    /// An empty prefix suggests the most used tags.
+    /// [br]
+    /// [param prefix]: [String] Start of the tag.
+    /// [br][param limit]: [int] Maximum number of suggestions.
+    #[func]
+    fn suggest_tags(&mut self, prefix: GString, limit: i64) -> PackedStringArray {
+        self.last_error = godot::global::Error::OK;
+
+        match self
+            .get_connection()
+            .and_then(|conn| tags::suggest(&conn, &prefix.to_string(), limit.max(0), &self.scope_clause()))
+        {
+            Ok(suggestions) => suggestions.iter().map(|s| GString::from(s.as_str())).collect(),
+            Err(e) => {
+                godot_error!("Failed to suggest tags: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+                PackedStringArray::new()
+            }
+        }
+    }
+
+    /// Suggest pack and individual asset names starting with [param prefix], ignoring case and accents, in name order.
+    /// [br]
+    /// [param prefix]: [String] Start of the name. An empty prefix suggests nothing.
+    /// [br][param limit]: [int] Maximum number of suggestions.
+    #[func]
+    fn suggest_names(&mut self, prefix: GString, limit: i64) -> PackedStringArray {
+        self.last_error = godot::global::Error::OK;
+
+        let prefix = prefix.to_string();
+        if prefix.is_empty() {
+            return PackedStringArray::new();
+        }
+
+        match self.fetch_name_suggestions(&prefix, limit.max(0)) {
+            Ok(suggestions) => suggestions.iter().map(|s| GString::from(s.as_str())).collect(),
+            Err(e) => {
+                godot_error!("Failed to suggest names: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+                PackedStringArray::new()
+            }
+        }
+    }
+
+    /// Make [param alias] a synonym of [param tag]: searching [code]tag:[/code] for either matches both.
+    /// If [param tag] is itself an alias, [param alias] becomes an alias of the tag it stands for.
+    /// [br][b]Returns:[/b] [constant ERR_INVALID_PARAMETER] if either is blank, or [param alias] is the tag [param tag] stands for.
+    #[func]
+    fn add_tag_alias(&mut self, alias: GString, tag: GString) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        match self
+            .get_connection()
+            .and_then(|mut conn| tags::add_alias(&mut conn, &alias.to_string(), &tag.to_string()))
+        {
+            Ok(true) => {}
+            Ok(false) => self.last_error = godot::global::Error::ERR_INVALID_PARAMETER,
+            Err(e) => {
+                godot_error!("Failed to add tag alias: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+            }
+        }
+        self.last_error
+    }
+
+    /// Remove an alias added with [method add_tag_alias].
+    #[func]
+    fn remove_tag_alias(&mut self, alias: GString) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        match self.get_connection().and_then(|conn| tags::remove_alias(&conn, &alias.to_string())) {
+            Ok(true) => {}
+            Ok(false) => self.last_error = godot::global::Error::ERR_DOES_NOT_EXIST,
+            Err(e) => {
+                godot_error!("Failed to remove tag alias: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+            }
+        }
+        self.last_error
+    }
+
+    /// Get every tag alias.
+    /// [b]Returns:[/b] [Dictionary] mapping each alias to the tag it stands for, both in lower case.
+    #[func]
+    fn get_tag_aliases(&mut self) -> VarDictionary {
+        self.last_error = godot::global::Error::OK;
+
+        let mut dict = VarDictionary::new();
+        match self.get_connection().and_then(|conn| tags::aliases(&conn)) {
+            Ok(aliases) => {
+                for (alias, tag) in aliases {
+                    dict.set(alias.as_str(), tag.as_str());
+                }
+            }
+            Err(e) => {
+                godot_error!("Failed to list tag aliases: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+            }
+        }
+        dict
+    }
+
+    /// Put [param tag] below [param parent], so searching [code]tag:[/code] for the parent matches it too.
+    /// Tags named [code]parent/child[/code] are below [code]parent[/code] without this.
+    /// [br]
+    /// [param tag]: [String] Child tag.
+    /// [br][param parent]: [String] Parent tag, or empty to remove the parent.
+    /// [br][b]Returns:[/b] [constant ERR_INVALID_PARAMETER] if [param parent] is [param tag] or below it.
+    #[func]
+    fn set_tag_parent(&mut self, tag: GString, parent: GString) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        match self
+            .get_connection()
+            .and_then(|conn| tags::set_parent(&conn, &tag.to_string(), &parent.to_string()))
+        {
+            Ok(true) => {}
+            Ok(false) => self.last_error = godot::global::Error::ERR_INVALID_PARAMETER,
+            Err(e) => {
+                godot_error!("Failed to set tag parent: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+            }
+        }
+        self.last_error
+    }
+
+    /// Get the parents set with [method set_tag_parent].
+    /// [b]Returns:[/b] [Dictionary] mapping each tag to its parent, both in lower case.
+    #[func]
+    fn get_tag_parents(&mut self) -> VarDictionary {
+        self.last_error = godot::global::Error::OK;
+
+        let mut dict = VarDictionary::new();
+        match self.get_connection().and_then(|conn| tags::parents(&conn)) {
+            Ok(parents) => {
+                for (tag, parent) in parents {
+                    dict.set(tag.as_str(), parent.as_str());
+                }
+            }
+            Err(e) => {
+                godot_error!("Failed to list tag parents: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+            }
+        }
+        dict
+    }
+
+    /// Save what was curated in the library to a JSON file, to back it up or share it: the names, previews,
+    /// tags and import times of packs and individual assets, the deleted list, and tag aliases and parents.
+    ///
+    /// Paths are written relative to the library root they're under, with the root's label, so
+    /// [method import_metadata] finds the same assets where the library is somewhere else.
+    /// [br]
+    /// [param path]: [String] File to write.
+    /// [br][b]Returns:[/b] [Error] OK, [code]ERR_DATABASE_CANT_READ[/code] or [code]ERR_FILE_CANT_WRITE[/code].
+    #[func]
+    fn export_metadata(&mut self, path: GString) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
+
+        let exported = match self.get_connection().and_then(|conn| metadata::export(&conn)) {
+            Ok(exported) => exported,
+            Err(e) => {
+                godot_error!("Failed to export metadata: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+                return self.last_error;
+            }
+        };
+
+        let real_path = Self::globalize(&path);
+        let written = serde_json::to_string_pretty(&exported)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&real_path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            godot_error!("Failed to write {}: {}", real_path, e);
+            self.last_error = godot::global::Error::ERR_FILE_CANT_WRITE;
+        }
+        self.last_error
+    }
+
+    /// Apply a file written by [method export_metadata] to the assets in the library.
+    ///
+    /// Entries are matched by their path relative to a library root with the same label, or to any root if none
+    /// has it. Entries without a match are not added: scan the library, then import again. Paths on the file's
+    /// deleted list are added to it.
+    /// [br]
+    /// [param path]: [String] File to read.
+    /// [br][param merge_policy]: [String] What to do where the file and the library disagree:
+    /// [code]"replace"[/code] takes the file's names, previews and tags, and removes assets the file has deleted;
+    /// [code]"merge"[/code] does the same but keeps the library's tags too; [code]"keep"[/code] only fills in
+    /// previews and tags the library doesn't have, keeps assets the file has deleted, and doesn't change existing
+    /// tag aliases and parents. Either way, the later import time is kept.
+    /// [br][b]Returns:[/b] [Dictionary] with [code]assets_updated[/code], [code]individual_assets_updated[/code],
+    /// [code]unmatched[/code] (a [PackedStringArray] of [code]root:path[/code] entries without exactly one matching
+    /// asset), [code]deleted_added[/code], [code]removed[/code], [code]tag_aliases[/code] and
+    /// [code]tag_parents[/code]. Empty on error: [constant ERR_INVALID_PARAMETER] for an unknown policy,
+    /// [code]ERR_FILE_CANT_READ[/code], [code]ERR_PARSE_ERROR[/code] for a file that isn't exported metadata,
+    /// [code]ERR_FILE_UNRECOGNIZED[/code] for one from a later version, or [code]ERR_DATABASE_CANT_WRITE[/code].
+    #[func]
+    fn import_metadata(&mut self, path: GString, merge_policy: GString) -> VarDictionary {
+        self.last_error = godot::global::Error::OK;
+
+        let Some(policy) = metadata::MergePolicy::parse(&merge_policy.to_string()) else {
+            godot_error!("Unknown merge policy: {}", merge_policy);
+            self.last_error = godot::global::Error::ERR_INVALID_PARAMETER;
+            return VarDictionary::new();
+        };
+
+        let real_path = Self::globalize(&path);
+        let content = match std::fs::read_to_string(&real_path) {
+            Ok(content) => content,
+            Err(e) => {
+                godot_error!("Failed to read {}: {}", real_path, e);
+                self.last_error = godot::global::Error::ERR_FILE_CANT_READ;
+                return VarDictionary::new();
+            }
+        };
+        let imported: metadata::Metadata = match serde_json::from_str(&content) {
+            Ok(imported) => imported,
+            Err(e) => {
+                godot_error!("Failed to parse {}: {}", real_path, e);
+                self.last_error = godot::global::Error::ERR_PARSE_ERROR;
+                return VarDictionary::new();
+            }
+        };
+        if imported.version > metadata::VERSION {
+            godot_error!("{} was exported by a later version (format {})", real_path, imported.version);
+            self.last_error = godot::global::Error::ERR_FILE_UNRECOGNIZED;
+            return VarDictionary::new();
+        }
+
+        let report = match self.get_connection().and_then(|mut conn| metadata::import(&mut conn, &imported, policy)) {
+            Ok(report) => report,
+            Err(e) => {
+                godot_error!("Failed to import metadata: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+                return VarDictionary::new();
+            }
+        };
+
+        let unmatched: PackedStringArray = report.unmatched.iter().map(|p| GString::from(p.as_str())).collect();
+        let mut dict = VarDictionary::new();
+        dict.set("assets_updated", report.assets_updated);
+        dict.set("individual_assets_updated", report.individual_assets_updated);
+        dict.set("unmatched", &unmatched);
+        dict.set("deleted_added", report.deleted_added);
+        dict.set("removed", report.removed);
+        dict.set("tag_aliases", report.tag_aliases);
+        dict.set("tag_parents", report.tag_parents);
+        dict
+    }
+
     // Helper methods (not exposed to GDScript)
 
-    fn get_connection(&self) -> SqlResult<Connection> {
-        Connection::open(&self.db_path)
+    fn get_connection(&self) -> SqlResult<db::PooledConnection<'_>> {
+        self.pool.get()
     }
 
-    fn init_database(&self) -> SqlResult<()> {
-        let conn = self.get_connection()?;
+    /// Convert a Godot path (user://, res://, etc.) to a real filesystem path.
+    fn globalize(path: &GString) -> String {
+        let path_str = path.to_string();
+        if path_str.starts_with("user://") || path_str.starts_with("res://") {
+            ProjectSettings::singleton()
+                .globalize_path(path)
+                .to_string()
+        } else {
+            path_str
+        }
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS assets (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                path TEXT NOT NULL UNIQUE,
-                image_path TEXT,
-                tags TEXT
-            )",
-            [],
-        )?;
+    /// Set `imported_at` of row [id] of [table] to now.
+    fn mark_imported_in(&mut self, table: &str, id: i64) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS deleted (
-                path TEXT PRIMARY KEY
-            )",
-            [],
-        )?;
+        let result = self.get_connection().and_then(|conn| {
+            conn.execute(&format!("UPDATE {} SET imported_at = unixepoch() WHERE id = ?1", table), params![id])
+        });
+        match result {
+            Ok(0) => self.last_error = godot::global::Error::ERR_DOES_NOT_EXIST,
+            Ok(_) => {}
+            Err(e) => {
+                godot_error!("Failed to mark asset as imported: {}", e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_WRITE;
+            }
+        }
+        self.last_error
+    }
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_assets_name ON assets(name)",
-            [],
-        )?;
+    /// Rows of [table] in scope (see `scope_clause`), or 0 if the database can't be read.
+    fn count_rows(&self, table: &str) -> i64 {
+        self.get_connection()
+            .and_then(|conn| {
+                let mut stmt = conn.prepare_cached(&format!("SELECT COUNT(*) FROM {} WHERE {}", table, self.scope_clause()))?;
+                stmt.query_row([], |row| row.get(0))
+            })
+            .unwrap_or(0)
+    }
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_assets_path ON assets(path)",
-            [],
-        )?;
+    /// SQL condition limiting asset rows to the root filter, and to enabled roots when there is none.
+    fn scope_clause(&self) -> String {
+        match self.root_filter {
+            Some(root_id) => format!("root_id = {}", root_id),
+            None => "(root_id IS NULL OR root_id IN (SELECT id FROM roots WHERE enabled = 1))".to_string(),
+        }
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS individual_assets (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                path TEXT NOT NULL UNIQUE,
-                image_path TEXT,
-                tags TEXT
-            )",
-            [],
-        )?;
+    fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            preview_file_names: self.preview_file_names.clone(),
+            use_first_image: self.use_first_image,
+            use_folder_name: self.use_folder_name,
+            ignore_patterns: self.ignore_patterns.clone(),
+            ignore_root: None,
+            parallel: self.parallel_scan,
+            // Walking every pack for its size is only worth it when the list is sorted by it.
+            disk_usage: matches!(self.sort_order.key, SortKey::Modified | SortKey::Size),
+        }
+    }
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_individual_assets_name ON individual_assets(name)",
-            [],
-        )?;
+    fn start_scan_job(&mut self, real_path: String, kind: ScanKind) -> godot::global::Error {
+        self.last_error = godot::global::Error::OK;
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_individual_assets_path ON individual_assets(path)",
-            [],
-        )?;
+        if self.scan_job.is_some() {
+            self.last_error = godot::global::Error::ERR_BUSY;
+            return self.last_error;
+        }
+
+        let job = match ScanJob::spawn(self.db_path.clone(), real_path, kind, self.scan_options()) {
+            Ok(job) => job,
+            Err(e) => {
+                godot_error!("Failed to start scan thread: {}", e);
+                self.last_error = godot::global::Error::ERR_CANT_CREATE;
+                return self.last_error;
+            }
+        };
+        self.scan_job = Some(job);
+
+        self.set_polling("_poll_scan", true);
+
+        godot::global::Error::OK
+    }
 
-        // Migration: Add tags column if it doesn't exist (for existing databases)
-        let has_tags = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('assets') WHERE name='tags'",
-            [],
-            |row| row.get::<_, i64>(0),
-        ).unwrap_or(0);
+    /// Connect or disconnect [method] to [signal SceneTree.process_frame].
+    ///
+    /// RefCounted has no process callback, so background work is polled from the scene tree. The connection only
+    /// holds the object id; Godot drops it automatically if this instance is freed while the work runs.
+    fn set_polling(&self, method: &str, enabled: bool) {
+        let Some(mut tree) = Engine::singleton().get_main_loop().and_then(|l| l.try_cast::<SceneTree>().ok()) else {
+            return;
+        };
+        let callable = Callable::from_object_method(&self.to_gd(), method);
+        let connected = tree.is_connected("process_frame", &callable);
+        if enabled && !connected {
+            tree.connect("process_frame", &callable);
+        } else if !enabled && connected {
+            tree.disconnect("process_frame", &callable);
+        }
+    }
 
-        if has_tags == 0 {
-            conn.execute("ALTER TABLE assets ADD COLUMN tags TEXT DEFAULT '[]'", [])?;
-            // Update existing NULL values to empty array
-            conn.execute("UPDATE assets SET tags = '[]' WHERE tags IS NULL", [])?;
+    fn finish_scan_job(&mut self, summary: &ScanSummary) -> VarDictionary {
+        let Some(mut job) = self.scan_job.take() else {
+            return VarDictionary::new();
+        };
+        job.join();
+
+        self.set_polling("_poll_scan", false);
+
+        let mut dict = Self::scan_report(summary);
+        dict.set("kind", job.kind.name());
+        dict.set("path", job.path.as_str());
+        if matches!(job.kind, ScanKind::Recovery(_)) {
+            match self.get_connection().and_then(|conn| recovery::recovered_counts(&conn)) {
+                Ok((assets, from_asset_json, individual_assets)) => {
+                    dict.set("assets_recovered", assets);
+                    dict.set("from_asset_json", from_asset_json);
+                    dict.set("individual_assets_recovered", individual_assets);
+                }
+                Err(e) => godot_error!("Failed to count recovered assets: {}", e),
+            }
         }
+        dict
+    }
 
-        Ok(())
+    fn scan_report(summary: &ScanSummary) -> VarDictionary {
+        let issues = |list: &[ScanIssue]| {
+            let mut array = VarArray::new();
+            for issue in list {
+                let mut dict = VarDictionary::new();
+                dict.set("path", issue.path.as_str());
+                dict.set("error", issue.message.as_str());
+                array.push(&dict.to_variant());
+            }
+            array
+        };
+
+        let mut dict = VarDictionary::new();
+        dict.set("dirs_visited", summary.dirs_visited);
+        dict.set("assets_found", summary.assets_found);
+        dict.set("updated", summary.updated);
+        dict.set("pruned", summary.pruned);
+        dict.set("skipped_existing", summary.skipped_existing);
+        dict.set("skipped_deleted", summary.skipped_deleted);
+        dict.set("nested_removed", summary.nested_removed);
+        dict.set("auto_filled", summary.auto_filled);
+        dict.set("parse_failures", &issues(&summary.parse_failures));
+        dict.set("io_errors", &issues(&summary.io_errors));
+        dict.set("write_errors", &issues(&summary.write_errors));
+        dict.set("elapsed_ms", summary.elapsed_ms);
+        dict.set("cancelled", summary.cancelled);
+        dict
+    }
+
+    fn init_database(&self) -> SqlResult<()> {
+        let mut conn = self.get_connection()?;
+        migrations::migrate(&mut conn)
     }
 
     fn insert_asset(&self, name: &str, path: &str, image_path: Option<&str>, tags: &[String]) -> SqlResult<i64> {
         let conn = self.get_connection()?;
-        let tags_json = serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string());
+        let tx = conn.unchecked_transaction()?;
 
-        conn.execute(
-            "INSERT INTO assets (name, path, image_path, tags) VALUES (?1, ?2, ?3, ?4)",
-            params![name, path, image_path, tags_json],
+        tx.execute(
+            "INSERT INTO assets (name, path, name_key, path_key, name_tokens, path_tokens, natural_key, image_path, discovered_by, added_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 'manual', unixepoch())",
+            params![
+                name,
+                path,
+                search_key(name),
+                search_key(path),
+                split_tokens(name),
+                split_tokens(path),
+                natural_key(name),
+                image_path
+            ],
         )?;
+        let id = tx.last_insert_rowid();
+        tags::set_tags(&tx, "assets", id, tags)?;
+        tx.commit()?;
 
-        Ok(conn.last_insert_rowid())
+        Ok(id)
     }
 
     fn fetch_asset(&self, id: i64) -> SqlResult<Option<AssetData>> {
         let conn = self.get_connection()?;
 
-        let result = conn.query_row(
-            "SELECT id, name, path, image_path, tags FROM assets WHERE id = ?1",
-            params![id],
-            |row| {
-                let id: i64 = row.get(0)?;
-                let name: String = row.get(1)?;
-                let path: String = row.get(2)?;
-                let image_path: Option<String> = row.get(3)?;
-                let tags_json: Option<String> = row.get(4)?;
-                let tags: Vec<String> = tags_json
-                    .and_then(|json| serde_json::from_str(&json).ok())
-                    .unwrap_or_default();
-
-                Ok(AssetData {
-                    id: Some(id),
-                    name,
-                    path,
-                    image_path,
-                    tags,
-                })
-            },
-        );
+        let mut stmt = conn.prepare_cached(&format!("SELECT {} FROM assets WHERE id = ?1", ASSET_COLUMNS))?;
+        let result = stmt.query_row(params![id], Self::row_to_asset);
 
         match result {
             Ok(asset) => Ok(Some(asset)),
@@ -702,28 +1795,14 @@ impl AssetManager {
 
     fn fetch_assets_page(&self, offset: i64, limit: i64) -> SqlResult<Vec<AssetData>> {
         let conn = self.get_connection()?;
-        let mut stmt = conn.prepare(
-            "SELECT id, name, path, image_path, tags FROM assets ORDER BY name COLLATE NOCASE LIMIT ?1 OFFSET ?2"
-        )?;
-
-        let assets = stmt.query_map(params![limit, offset], |row| {
-            let id: i64 = row.get(0)?;
-            let name: String = row.get(1)?;
-            let path: String = row.get(2)?;
-            let image_path: Option<String> = row.get(3)?;
-            let tags_json: Option<String> = row.get(4)?;
-            let tags: Vec<String> = tags_json
-                .and_then(|json| serde_json::from_str(&json).ok())
-                .unwrap_or_default();
-
-            Ok(AssetData {
-                id: Some(id),
-                name,
-                path,
-                image_path,
-                tags,
-            })
-        })?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM assets WHERE {} ORDER BY {} LIMIT ?1 OFFSET ?2",
+            ASSET_COLUMNS,
+            self.scope_clause(),
+            self.sort_order.to_sql()
+        ))?;
+
+        let assets = stmt.query_map(params![limit, offset], Self::row_to_asset)?;
 
         assets.collect()
     }
@@ -737,25 +1816,36 @@ impl AssetManager {
         tags: Option<&[String]>,
     ) -> SqlResult<()> {
         let conn = self.get_connection()?;
+        // One transaction, so a failed field leaves none of them changed.
+        let tx = conn.unchecked_transaction()?;
 
         if let Some(n) = name {
-            conn.execute("UPDATE assets SET name = ?1 WHERE id = ?2", params![n, id])?;
+            tx.execute(
+                "UPDATE assets SET name = ?1, name_key = ?2, name_tokens = ?3, natural_key = ?4 WHERE id = ?5",
+                params![n, search_key(n), split_tokens(n), natural_key(n), id],
+            )?;
         }
 
         if let Some(p) = path {
-            conn.execute("UPDATE assets SET path = ?1 WHERE id = ?2", params![p, id])?;
+            tx.execute(
+                "UPDATE assets SET path = ?1, path_key = ?2, path_tokens = ?3 WHERE id = ?4",
+                params![p, search_key(p), split_tokens(p), id],
+            )?;
         }
 
+        // A preview picked by hand replaces whatever rule found the old one.
         if let Some(img) = image_path {
-            conn.execute("UPDATE assets SET image_path = ?1 WHERE id = ?2", params![img, id])?;
+            tx.execute(
+                "UPDATE assets SET image_path = ?1, discovered_by = 'manual', discovered_pattern = NULL WHERE id = ?2",
+                params![img, id],
+            )?;
         }
 
         if let Some(t) = tags {
-            let tags_json = serde_json::to_string(t).unwrap_or_else(|_| "[]".to_string());
-            conn.execute("UPDATE assets SET tags = ?1 WHERE id = ?2", params![tags_json, id])?;
+            tags::set_tags(&tx, "assets", id, t)?;
         }
 
-        Ok(())
+        tx.commit()
     }
 
     fn remove_asset(&self, id: i64) -> SqlResult<()> {
@@ -770,312 +1860,376 @@ impl AssetManager {
         Ok(())
     }
 
-    fn search_assets(&self, query: &str, offset: i64, limit: i64) -> SqlResult<(Vec<AssetData>, i64)> {
-        let conn = self.get_connection()?;
-
-        // Parse query: separate regular terms from tag: terms
-        let mut general_terms: Vec<String> = Vec::new();
-        let mut tag_terms: Vec<String> = Vec::new();
-
-        for part in query.split_whitespace() {
-            if let Some(tag) = part.strip_prefix("tag:") {
-                if !tag.is_empty() {
-                    tag_terms.push(tag.to_string());
-                }
-            } else {
-                general_terms.push(part.to_string());
-            }
-        }
+    /// Run [query] against [target] and build the page dictionary shared by both searches.
+    fn search_page(&mut self, target: &SearchTarget, query: &str, page: i64) -> VarDictionary {
+        self.last_error = godot::global::Error::OK;
 
-        // Build WHERE clause dynamically
-        let mut conditions: Vec<String> = Vec::new();
-        let mut params_vec: Vec<String> = Vec::new();
+        let page = page.max(1);
+        let offset = (page - 1) * self.page_size;
 
-        for term in &general_terms {
-            let pattern = format!("%{}%", term);
-            let idx = params_vec.len() + 1;
-            conditions.push(format!("(name LIKE ?{} OR path LIKE ?{} OR tags LIKE ?{})", idx, idx, idx));
-            params_vec.push(pattern);
-        }
+        let mut dict = VarDictionary::new();
+        dict.set("page_number", page);
+        dict.set("page_size", self.page_size);
 
-        for tag in &tag_terms {
-            let pattern = format!("%{}%", tag);
-            let idx = params_vec.len() + 1;
-            conditions.push(format!("tags LIKE ?{}", idx));
-            params_vec.push(pattern);
-        }
+        match self.search_results(target, query, offset, self.page_size) {
+            Ok((assets, total_count)) => {
+                dict.set("num_of_pages", (total_count + self.page_size - 1) / self.page_size);
+                dict.set("fuzzy", assets.first().is_some_and(|(_, score)| score.is_some()));
 
-        if conditions.is_empty() {
-            return Ok((Vec::new(), 0));
+                let mut assets_array = VarArray::new();
+                for (asset, score) in &assets {
+                    let mut asset_dict = self.asset_to_dict(asset);
+                    if let Some(score) = score {
+                        asset_dict.set("score", *score);
+                    }
+                    assets_array.push(&asset_dict.to_variant());
+                }
+                dict.set("assets", &assets_array);
+                dict
+            }
+            Err(SearchError::Parse(e)) => {
+                self.last_error = godot::global::Error::ERR_PARSE_ERROR;
+                dict.set("num_of_pages", 0);
+                dict.set("assets", &VarArray::new());
+                dict.set("error", e.message.as_str());
+                dict.set("error_position", e.position as i64);
+                dict
+            }
+            Err(SearchError::InvalidCursor) => {
+                self.last_error = godot::global::Error::ERR_INVALID_PARAMETER;
+                dict.set("error", "Invalid cursor");
+                dict
+            }
+            Err(SearchError::Sql(e)) => {
+                godot_error!("Failed to search {}: {}", target.table, e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+                VarDictionary::new()
+            }
         }
+    }
 
-        let where_clause = conditions.join(" AND ");
-        let count_sql = format!("SELECT COUNT(*) FROM assets WHERE {}", where_clause);
-        let search_sql = format!(
-            "SELECT id, name, path, image_path, tags FROM assets WHERE {} ORDER BY name COLLATE NOCASE LIMIT ?{} OFFSET ?{}",
-            where_clause,
-            params_vec.len() + 1,
-            params_vec.len() + 2
-        );
+    /// The batch of [target]'s assets after [cursor], matching [query] if there is one. See `get_assets_after`.
+    fn batch_after(&mut self, target: &SearchTarget, query: Option<&str>, cursor: &str, limit: i64) -> VarDictionary {
+        self.last_error = godot::global::Error::OK;
 
-        let total_count: i64 = {
-            let mut stmt = conn.prepare(&count_sql)?;
-            let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
-            stmt.query_row(params_refs.as_slice(), |row| row.get(0))?
+        let mut dict = VarDictionary::new();
+        let root_filter = self.root_filter.map(|id| id.to_string()).unwrap_or_default();
+        let signature = self.sort_order.signature();
+        let scope = cursor::scope_hash(&[target.table, query.unwrap_or(""), &signature, &root_filter]);
+        let cursor = if cursor.is_empty() {
+            None
+        } else {
+            match Cursor::decode(cursor) {
+                Some(cursor) if cursor.scope() == scope => Some(cursor),
+                Some(_) => {
+                    self.last_error = godot::global::Error::ERR_INVALID_PARAMETER;
+                    dict.set("error", "Cursor is from another query, order or root filter");
+                    return dict;
+                }
+                None => {
+                    self.last_error = godot::global::Error::ERR_INVALID_PARAMETER;
+                    dict.set("error", "Invalid cursor");
+                    return dict;
+                }
+            }
         };
 
-        let mut stmt = conn.prepare(&search_sql)?;
-        let mut all_params: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
-        all_params.push(&limit);
-        all_params.push(&offset);
-
-        let assets = stmt.query_map(all_params.as_slice(), |row| {
-            let id: i64 = row.get(0)?;
-            let name: String = row.get(1)?;
-            let path: String = row.get(2)?;
-            let image_path: Option<String> = row.get(3)?;
-            let tags_json: Option<String> = row.get(4)?;
-            let tags: Vec<String> = tags_json
-                .and_then(|json| serde_json::from_str(&json).ok())
-                .unwrap_or_default();
-
-            Ok(AssetData {
-                id: Some(id),
-                name,
-                path,
-                image_path,
-                tags,
-            })
-        })?;
-
-        let assets_vec: SqlResult<Vec<AssetData>> = assets.collect();
-        Ok((assets_vec?, total_count))
-    }
-
-    fn scan_directory(&self, base_path: String) -> SqlResult<()> {
-        use std::collections::HashSet;
-
-        let file_extensions = vec!["png", "jpeg", "jpg", "bmp", "tga", "webp", "svg"];
+        let result = match query {
+            Some(query) => query::parse(query).map_err(SearchError::from).and_then(|parsed| match parsed {
+                Some(parsed) => self.search_batch(target, &parsed, cursor.as_ref(), limit.max(1)),
+                None => Ok((Vec::new(), None)),
+            }),
+            None => self
+                .keyset_table(target, None, cursor.as_ref(), limit.max(1))
+                .map(|(assets, next)| (assets.into_iter().map(|asset| (asset, None)).collect(), next)),
+        };
 
-        // Same single-connection / single-transaction strategy as scan_individual_directory:
-        // opening a fresh connection per directory and committing every INSERT on its own made
-        // a fresh scan of a large tree slow. Preload the deleted/existing paths once, then do
-        // all writes inside one transaction.
-        let mut conn = self.get_connection()?;
-        let _ = conn.execute_batch("PRAGMA synchronous = OFF; PRAGMA journal_mode = MEMORY;");
+        match result {
+            Ok((assets, next)) => {
+                dict.set("next_cursor", next.map(|c| c.scoped(scope).encode()).unwrap_or_default());
+                dict.set("fuzzy", assets.first().is_some_and(|(_, score)| score.is_some()));
 
-        let mut deleted: HashSet<String> = HashSet::new();
-        {
-            let mut stmt = conn.prepare("SELECT path FROM deleted")?;
-            for row in stmt.query_map([], |row| row.get::<_, String>(0))?.flatten() {
-                deleted.insert(row);
+                let mut assets_array = VarArray::new();
+                for (asset, score) in &assets {
+                    let mut asset_dict = self.asset_to_dict(asset);
+                    if let Some(score) = score {
+                        asset_dict.set("score", *score);
+                    }
+                    assets_array.push(&asset_dict.to_variant());
+                }
+                dict.set("assets", &assets_array);
+                dict
+            }
+            Err(SearchError::Parse(e)) => {
+                self.last_error = godot::global::Error::ERR_PARSE_ERROR;
+                dict.set("assets", &VarArray::new());
+                dict.set("next_cursor", "");
+                dict.set("error", e.message.as_str());
+                dict.set("error_position", e.position as i64);
+                dict
+            }
+            Err(SearchError::InvalidCursor) => {
+                self.last_error = godot::global::Error::ERR_INVALID_PARAMETER;
+                dict.set("error", "Invalid cursor");
+                dict
+            }
+            Err(SearchError::Sql(e)) => {
+                godot_error!("Failed to read {}: {}", target.table, e);
+                self.last_error = godot::global::Error::ERR_DATABASE_CANT_READ;
+                VarDictionary::new()
             }
         }
+    }
 
-        let mut existing: HashSet<String> = HashSet::new();
-        {
-            let mut stmt = conn.prepare("SELECT path FROM assets")?;
-            for row in stmt.query_map([], |row| row.get::<_, String>(0))?.flatten() {
-                existing.insert(row);
+    /// The batch of results for [parsed] after [cursor], and the cursor to the next batch if there is one.
+    /// A first batch is fuzzy under the same conditions as `search_results`; later ones follow their cursor.
+    fn search_batch(
+        &self,
+        target: &SearchTarget,
+        parsed: &Query,
+        cursor: Option<&Cursor>,
+        limit: i64,
+    ) -> Result<(ScoredAssets, Option<Cursor>), SearchError> {
+        let fuzzy = self.fuzzy_search || cursor.is_some_and(|c| c.order == cursor::FUZZY_ORDER);
+        if !fuzzy {
+            let (assets, next) = self.keyset_table(target, Some(parsed), cursor, limit)?;
+            if !assets.is_empty() || cursor.is_some() {
+                return Ok((assets.into_iter().map(|asset| (asset, None)).collect(), next));
             }
         }
 
-        let tx = conn.transaction()?;
-
-        let mut walker = WalkDir::new(&base_path)
-            .follow_links(false)
-            .into_iter();
-
-        while let Some(entry) = walker.next() {
-            let entry = match entry {
-                Ok(e) => e,
-                Err(_) => continue,
-            };
-
-            let path = entry.path();
+        let after = match cursor {
+            Some(cursor) => match cursor.values(cursor::FUZZY_ORDER, 4).as_deref() {
+                Some([Value::Real(score), Value::Text(key), Value::Text(name), Value::Integer(id)]) => {
+                    Some((*score, key.clone(), name.clone(), *id))
+                }
+                _ => return Err(SearchError::InvalidCursor),
+            },
+            None => None,
+        };
 
-            if !path.is_dir() {
+        let mut matches: ScoredAssets = Vec::new();
+        let mut last_key = None;
+        for (asset, score) in self.fuzzy_matches(target, parsed)? {
+            let key = Self::fuzzy_key(&asset, score);
+            if after.as_ref().is_some_and(|after| Self::compare_fuzzy_keys(&key, after).is_le()) {
                 continue;
             }
-
-            // Skip macOS resource-fork folders (and everything inside them)
-            if path.components().any(|c| c.as_os_str() == "__MACOSX") {
-                walker.skip_current_dir();
-                continue;
+            if matches.len() as i64 == limit {
+                // One more result than asked for: there is a next batch.
+                let (score, key, name, id) = last_key.unwrap_or(key);
+                let next = Cursor::new(
+                    cursor::FUZZY_ORDER,
+                    &[Value::Real(score), Value::Text(key), Value::Text(name), Value::Integer(id)],
+                );
+                return Ok((matches, Some(next)));
             }
+            last_key = Some(key);
+            matches.push((asset, Some(score)));
+        }
+        Ok((matches, None))
+    }
 
-            let path_str = path.to_string_lossy().to_string();
+    /// Up to [limit] rows of [target]'s table after [cursor], matching [parsed] if given, in the
+    /// order set with `set_sort_order` or by relevance. Also returns the cursor to the next batch.
+    fn keyset_table(
+        &self,
+        target: &SearchTarget,
+        parsed: Option<&Query>,
+        cursor: Option<&Cursor>,
+        limit: i64,
+    ) -> Result<(Vec<AssetData>, Option<Cursor>), SearchError> {
+        let conn = self.get_connection()?;
+        let mut params_vec: Vec<String> = Vec::new();
+        let mut where_clause = self.scope_clause();
+        if let Some(parsed) = parsed {
+            where_clause = format!("{} AND {}", parsed.to_sql(target, &mut params_vec), where_clause);
+        }
 
-            // Check if already deleted
-            if deleted.contains(&path_str) {
-                walker.skip_current_dir();
-                continue;
+        let by_relevance = self.sort_by_relevance && self.sort_order.is_by_name();
+        let rank = if by_relevance { parsed.and_then(Query::rank_expression) } else { None };
+        let (source, terms, order): (String, Vec<SortTerm>, String) = match rank {
+            Some(expression) => {
+                params_vec.push(expression);
+                (
+                    search_index::ranked_source(target.table, params_vec.len()),
+                    sort::relevance_terms(),
+                    cursor::RELEVANCE_ORDER.to_string(),
+                )
             }
+            None => (target.table.to_string(), self.sort_order.terms(), self.sort_order.signature()),
+        };
 
-            // Check if path already exists in database - skip to speed up rescanning
-            if existing.contains(&path_str) {
-                walker.skip_current_dir();
-                continue;
-            }
+        let mut values: Vec<Value> = params_vec.into_iter().map(Value::Text).collect();
+        if let Some(cursor) = cursor {
+            let after = cursor.values(&order, terms.len()).ok_or(SearchError::InvalidCursor)?;
+            where_clause = format!("{} AND {}", where_clause, cursor::after_clause(&terms, &after, values.len() + 1));
+            values.extend(after);
+        }
+        // One row more than asked for tells whether there is a next batch.
+        values.push(Value::Integer(limit + 1));
+
+        let columns = if target.is_packs { ASSET_COLUMNS } else { INDIVIDUAL_ASSET_COLUMNS };
+        let sort_columns: Vec<&str> = terms.iter().map(|term| term.expression.as_str()).collect();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {}, {} FROM {} WHERE {} ORDER BY {} LIMIT ?{}",
+            columns,
+            sort_columns.join(", "),
+            source,
+            where_clause,
+            sort::order_by(&terms),
+            values.len()
+        ))?;
+
+        let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+            let asset = Self::row_to_asset(row)?;
+            let sort_values = (0..terms.len())
+                .map(|i| row.get::<_, Value>(ASSET_COLUMN_COUNT + i))
+                .collect::<SqlResult<Vec<Value>>>()?;
+            Ok((asset, sort_values))
+        })?;
+        let mut rows = rows.collect::<SqlResult<Vec<_>>>()?;
 
-            // Check for Asset.json
-            let asset_json = path.join("Asset.json");
-            let has_asset_json = asset_json.exists();
+        let mut next = None;
+        if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            next = rows.last().map(|(_, sort_values)| Cursor::new(order, sort_values));
+        }
+        Ok((rows.into_iter().map(|(asset, _)| asset).collect(), next))
+    }
 
-            if has_asset_json {
-                // Remove any previously-indexed assets living in subdirectories of this pack.
-                // Within the open transaction this also sees rows inserted earlier in this scan.
-                let pattern = format!("{}/%", path_str);
-                if let Ok(removed) = tx.execute("DELETE FROM assets WHERE path LIKE ?1", params![pattern]) {
-                    if removed > 0 {
-                        godot_print!("AssetManager: Removed {} assets from subdirectories of {}", removed, path_str);
-                    }
-                }
+    /// One page of results for [query] and the total count. Results are exact, or fuzzy with their score
+    /// if fuzzy search is on or nothing matched exactly.
+    fn search_results(
+        &self,
+        target: &SearchTarget,
+        query: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(ScoredAssets, i64), SearchError> {
+        let Some(parsed) = query::parse(query)? else {
+            return Ok((Vec::new(), 0));
+        };
 
-                if let Ok(content) = std::fs::read_to_string(&asset_json) {
-                    let asset_json_data = serde_json::from_str::<AssetData>(&content).ok();
-
-                    if let Some(ref data) = asset_json_data {
-                        if !data.name.is_empty() && !data.path.is_empty() {
-                            let tags_json = serde_json::to_string(&data.tags)
-                                .unwrap_or_else(|_| "[]".to_string());
-                            let _ = tx.execute(
-                                "INSERT INTO assets (name, path, image_path, tags) VALUES (?1, ?2, ?3, ?4)",
-                                params![data.name, data.path, data.image_path.as_deref(), tags_json],
-                            );
-                            walker.skip_current_dir();
-                            continue;
-                        }
-                    }
-                }
+        if !self.fuzzy_search {
+            let (assets, total_count) = self.search_table(target, &parsed, offset, limit)?;
+            if total_count > 0 {
+                return Ok((assets.into_iter().map(|asset| (asset, None)).collect(), total_count));
             }
+        }
 
-            // Look for preview image files
-            let folder_name = path
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| "Unknown".to_string());
-
-            let mut found_image: Option<String> = None;
-            let mut first_image: Option<String> = None;
-
-            if let Ok(entries) = std::fs::read_dir(path) {
-                let files: Vec<_> = entries.filter_map(|e| e.ok()).collect();
-
-                // First pass: look for specific preview file names (supports regex)
-                for preview_pattern in &self.preview_file_names {
-                    // Check if pattern is regex (starts with '^')
-                    let is_regex = preview_pattern.starts_with('^');
-
-                    if is_regex {
-                        // Use regex matching - user has full control (use (?i) in pattern for case-insensitive)
-                        if let Ok(re) = Regex::new(&preview_pattern) {
-                            for file_entry in &files {
-                                let filename = file_entry.file_name().to_string_lossy().to_string();
-                                if re.is_match(&filename) {
-                                    // Verify it's an image file
-                                    if let Some(ext) = file_entry.path().extension() {
-                                        let ext_str = ext.to_string_lossy().to_lowercase();
-                                        if file_extensions.contains(&ext_str.as_str()) {
-                                            found_image = Some(file_entry.path().to_string_lossy().to_string());
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        // Use literal matching - exact filename match (case-insensitive)
-                        // "Preview" matches "Preview.png", "preview.jpg" but NOT "Preview1.png"
-                        for file_entry in &files {
-                            if let Some(stem) = file_entry.path().file_stem() {
-                                let stem_str = stem.to_string_lossy().to_string();
-
-                                // Match if stem equals the pattern exactly (case-insensitive)
-                                if stem_str.eq_ignore_ascii_case(&preview_pattern) {
-                                    // Verify it's an image file
-                                    if let Some(ext) = file_entry.path().extension() {
-                                        let ext_str = ext.to_string_lossy().to_lowercase();
-                                        if file_extensions.contains(&ext_str.as_str()) {
-                                            found_image = Some(file_entry.path().to_string_lossy().to_string());
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    if found_image.is_some() {
-                        break;
-                    }
-                }
+        let (assets, total_count) = self.fuzzy_search_table(target, &parsed, offset, limit)?;
+        Ok((assets.into_iter().map(|(asset, score)| (asset, Some(score))).collect(), total_count))
+    }
 
-                // Second pass: look for folder name as filename (if enabled)
-                if found_image.is_none() && self.use_folder_name {
-                    for ext in &file_extensions {
-                        let target_filename = format!("{}.{}", folder_name, ext);
+    fn search_table(&self, target: &SearchTarget, parsed: &Query, offset: i64, limit: i64) -> SqlResult<(Vec<AssetData>, i64)> {
+        let conn = self.get_connection()?;
+        let mut params_vec: Vec<String> = Vec::new();
+        let where_clause = format!("{} AND {}", parsed.to_sql(target, &mut params_vec), self.scope_clause());
 
-                        if let Some(file_entry) = files.iter().find(|f| {
-                            f.file_name().to_string_lossy().eq_ignore_ascii_case(&target_filename)
-                        }) {
-                            found_image = Some(file_entry.path().to_string_lossy().to_string());
-                            break;
-                        }
-                    }
-                }
+        let total_count: i64 = {
+            let count_sql = format!("SELECT COUNT(*) FROM {} WHERE {}", target.table, where_clause);
+            let mut stmt = conn.prepare_cached(&count_sql)?;
+            let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
+            stmt.query_row(params_refs.as_slice(), |row| row.get(0))?
+        };
 
-                // Third pass: use first image if enabled OR if we have empty Asset.json
-                if found_image.is_none() && (self.use_first_image || has_asset_json) {
-                    for file_entry in &files {
-                        if let Some(ext) = file_entry.path().extension() {
-                            let ext_str = ext.to_string_lossy().to_lowercase();
-                            if file_extensions.contains(&ext_str.as_str()) {
-                                first_image = Some(file_entry.path().to_string_lossy().to_string());
-                                break;
-                            }
-                        }
-                    }
-                }
+        // Rank by the full-text terms that aren't excluded, if there are any, unless another order was picked.
+        let by_relevance = self.sort_by_relevance && self.sort_order.is_by_name();
+        let rank = if by_relevance { parsed.rank_expression() } else { None };
+        let (source, order) = match rank {
+            Some(expression) => {
+                params_vec.push(expression);
+                (
+                    search_index::ranked_source(target.table, params_vec.len()),
+                    "fts.rank IS NULL, fts.rank, name_key, name".to_string(),
+                )
             }
+            None => (target.table.to_string(), self.sort_order.to_sql()),
+        };
+
+        let columns = if target.is_packs { ASSET_COLUMNS } else { INDIVIDUAL_ASSET_COLUMNS };
+        let search_sql = format!(
+            "SELECT {} FROM {} WHERE {} ORDER BY {} LIMIT ?{} OFFSET ?{}",
+            columns,
+            source,
+            where_clause,
+            order,
+            params_vec.len() + 1,
+            params_vec.len() + 2
+        );
 
-            // Insert asset if we found an image
-            let final_image = found_image.or(first_image);
+        let mut stmt = conn.prepare_cached(&search_sql)?;
+        let mut all_params: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
+        all_params.push(&limit);
+        all_params.push(&offset);
 
-            // If we have an empty Asset.json file, write the auto-discovered data to it
-            if has_asset_json {
-                let auto_data = AssetData {
-                    id: None,
-                    name: folder_name.clone(),
-                    path: path_str.clone(),
-                    image_path: final_image.clone().or(Some(String::new())),  // Empty string if no image found
-                    tags: Vec::new(),
-                };
+        let assets = stmt.query_map(all_params.as_slice(), Self::row_to_asset)?;
 
-                // Write the auto-discovered data to Asset.json
-                if let Ok(json_content) = serde_json::to_string_pretty(&auto_data) {
-                    let _ = std::fs::write(&asset_json, json_content);
-                }
-            }
+        let assets_vec: SqlResult<Vec<AssetData>> = assets.collect();
+        Ok((assets_vec?, total_count))
+    }
 
-            if let Some(image_path) = final_image {
-                let _ = tx.execute(
-                    "INSERT INTO assets (name, path, image_path, tags) VALUES (?1, ?2, ?3, '[]')",
-                    params![folder_name, path_str, image_path],
-                );
-                walker.skip_current_dir();
-            } else if has_asset_json {
-                // Insert asset even without an image if Asset.json exists
-                let _ = tx.execute(
-                    "INSERT INTO assets (name, path, image_path, tags) VALUES (?1, ?2, NULL, '[]')",
-                    params![folder_name, path_str],
-                );
-                walker.skip_current_dir();
+    /// A page of `fuzzy_matches` and their total count.
+    fn fuzzy_search_table(
+        &self,
+        target: &SearchTarget,
+        parsed: &Query,
+        offset: i64,
+        limit: i64,
+    ) -> SqlResult<(Vec<(AssetData, f64)>, i64)> {
+        let matches = self.fuzzy_matches(target, parsed)?;
+        let total_count = matches.len() as i64;
+        let page = matches.into_iter().skip(offset.max(0) as usize).take(limit.max(0) as usize).collect();
+        Ok((page, total_count))
+    }
+
+    /// Rows sharing three-letter runs with the query's words, scored by `fuzzy::score` and filtered like
+    /// [parsed] apart from its full-text terms. Best first, see `compare_fuzzy_keys`.
+    fn fuzzy_matches(&self, target: &SearchTarget, parsed: &Query) -> SqlResult<Vec<(AssetData, f64)>> {
+        let words = parsed.fuzzy_words();
+        let Some(expression) = search_index::trigram_expression(&words) else {
+            return Ok(Vec::new());
+        };
+
+        let conn = self.get_connection()?;
+        let mut params_vec: Vec<String> = Vec::new();
+        let where_clause = format!("{} AND {}", parsed.to_sql_without_text(target, &mut params_vec), self.scope_clause());
+        params_vec.push(expression);
+
+        let columns = if target.is_packs { ASSET_COLUMNS } else { INDIVIDUAL_ASSET_COLUMNS };
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM {} WHERE {} ORDER BY fts.rank LIMIT {}",
+            columns,
+            search_index::trigram_source(target.table, params_vec.len()),
+            where_clause,
+            fuzzy::MAX_CANDIDATES
+        ))?;
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
+        let candidates = stmt.query_map(params_refs.as_slice(), Self::row_to_asset)?;
+
+        let mut scored = Vec::new();
+        for asset in candidates {
+            let asset = asset?;
+            let score = fuzzy::score(&words, &asset.name, &asset.tags);
+            if score >= fuzzy::MIN_SCORE {
+                let key = Self::fuzzy_key(&asset, score);
+                scored.push((key, asset, score));
             }
         }
+        scored.sort_by(|(a, ..), (b, ..)| Self::compare_fuzzy_keys(a, b));
+        Ok(scored.into_iter().map(|(_, asset, score)| (asset, score)).collect())
+    }
 
-        tx.commit()?;
-        Ok(())
+    fn fuzzy_key(asset: &AssetData, score: f64) -> FuzzyKey {
+        (score, search_key(&asset.name), asset.name.clone(), asset.id.unwrap_or_default())
+    }
+
+    fn compare_fuzzy_keys(a: &FuzzyKey, b: &FuzzyKey) -> std::cmp::Ordering {
+        b.0.total_cmp(&a.0)
+            .then_with(|| a.1.cmp(&b.1))
+            .then_with(|| a.2.cmp(&b.2))
+            .then_with(|| a.3.cmp(&b.3))
     }
 
     fn row_to_asset(row: &rusqlite::Row) -> SqlResult<AssetData> {
@@ -1087,6 +2241,10 @@ impl AssetManager {
         let tags: Vec<String> = tags_json
             .and_then(|json| serde_json::from_str(&json).ok())
             .unwrap_or_default();
+        let root_id: Option<i64> = row.get(5)?;
+        let discovered_by: Option<String> = row.get(6)?;
+        let discovered_pattern: Option<String> = row.get(7)?;
+        let license: Option<String> = row.get(8)?;
 
         Ok(AssetData {
             id: Some(id),
@@ -1094,17 +2252,18 @@ impl AssetManager {
             path,
             image_path,
             tags,
+            license,
+            root_id,
+            discovered_by,
+            discovered_pattern,
         })
     }
 
     fn fetch_individual_asset(&self, id: i64) -> SqlResult<Option<AssetData>> {
         let conn = self.get_connection()?;
 
-        let result = conn.query_row(
-            "SELECT id, name, path, image_path, tags FROM individual_assets WHERE id = ?1",
-            params![id],
-            |row| Self::row_to_asset(row),
-        );
+        let mut stmt = conn.prepare_cached(&format!("SELECT {} FROM individual_assets WHERE id = ?1", INDIVIDUAL_ASSET_COLUMNS))?;
+        let result = stmt.query_row(params![id], Self::row_to_asset);
 
         match result {
             Ok(asset) => Ok(Some(asset)),
@@ -1115,173 +2274,43 @@ impl AssetManager {
 
     fn fetch_individual_assets_page(&self, offset: i64, limit: i64) -> SqlResult<Vec<AssetData>> {
         let conn = self.get_connection()?;
-        let mut stmt = conn.prepare(
-            "SELECT id, name, path, image_path, tags FROM individual_assets ORDER BY name COLLATE NOCASE LIMIT ?1 OFFSET ?2"
-        )?;
-
-        let assets = stmt.query_map(params![limit, offset], |row| Self::row_to_asset(row))?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM individual_assets WHERE {} ORDER BY {} LIMIT ?1 OFFSET ?2",
+            INDIVIDUAL_ASSET_COLUMNS,
+            self.scope_clause(),
+            self.sort_order.to_sql()
+        ))?;
+
+        let assets = stmt.query_map(params![limit, offset], Self::row_to_asset)?;
         assets.collect()
     }
 
-    fn remove_individual_asset(&self, id: i64) -> SqlResult<()> {
-        let conn = self.get_connection()?;
-        conn.execute("DELETE FROM individual_assets WHERE id = ?1", params![id])?;
-        Ok(())
-    }
-
-    fn search_individual(&self, query: &str, offset: i64, limit: i64) -> SqlResult<(Vec<AssetData>, i64)> {
+    fn fetch_name_suggestions(&self, prefix: &str, limit: i64) -> SqlResult<Vec<String>> {
         let conn = self.get_connection()?;
-
-        // Parse query: separate regular terms from tag: terms
-        let mut general_terms: Vec<String> = Vec::new();
-        let mut tag_terms: Vec<String> = Vec::new();
-
-        for part in query.split_whitespace() {
-            if let Some(tag) = part.strip_prefix("tag:") {
-                if !tag.is_empty() {
-                    tag_terms.push(tag.to_string());
-                }
-            } else {
-                general_terms.push(part.to_string());
-            }
-        }
-
-        let mut conditions: Vec<String> = Vec::new();
-        let mut params_vec: Vec<String> = Vec::new();
-
-        for term in &general_terms {
-            let pattern = format!("%{}%", term);
-            let idx = params_vec.len() + 1;
-            conditions.push(format!("(name LIKE ?{} OR path LIKE ?{} OR tags LIKE ?{})", idx, idx, idx));
-            params_vec.push(pattern);
-        }
-
-        for tag in &tag_terms {
-            let pattern = format!("%{}%", tag);
-            let idx = params_vec.len() + 1;
-            conditions.push(format!("tags LIKE ?{}", idx));
-            params_vec.push(pattern);
-        }
-
-        if conditions.is_empty() {
-            return Ok((Vec::new(), 0));
-        }
-
-        let where_clause = conditions.join(" AND ");
-        let count_sql = format!("SELECT COUNT(*) FROM individual_assets WHERE {}", where_clause);
-        let search_sql = format!(
-            "SELECT id, name, path, image_path, tags FROM individual_assets WHERE {} ORDER BY name COLLATE NOCASE LIMIT ?{} OFFSET ?{}",
-            where_clause,
-            params_vec.len() + 1,
-            params_vec.len() + 2
-        );
-
-        let total_count: i64 = {
-            let mut stmt = conn.prepare(&count_sql)?;
-            let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
-            stmt.query_row(params_refs.as_slice(), |row| row.get(0))?
+        // A range instead of LIKE so the name key indexes are used. char::MAX sorts after anything a key can continue with.
+        let prefix = search_key(prefix);
+        let upper = format!("{}{}", prefix, char::MAX);
+        let arm = |table: &str| {
+            format!(
+                "SELECT * FROM (SELECT name_key, name FROM {} WHERE name_key >= ?1 AND name_key < ?2 AND {}
+                 ORDER BY name_key, name LIMIT ?3)",
+                table,
+                self.scope_clause()
+            )
         };
-
-        let mut stmt = conn.prepare(&search_sql)?;
-        let mut all_params: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
-        all_params.push(&limit);
-        all_params.push(&offset);
-
-        let assets = stmt.query_map(all_params.as_slice(), |row| Self::row_to_asset(row))?;
-        let assets_vec: SqlResult<Vec<AssetData>> = assets.collect();
-        Ok((assets_vec?, total_count))
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT name FROM ({} UNION {}) ORDER BY name_key, name LIMIT ?3",
+            arm("assets"),
+            arm("individual_assets")
+        ))?;
+
+        let names = stmt.query_map(params![prefix, upper, limit], |row| row.get(0))?;
+        names.collect()
     }
 
-    fn scan_individual_directory(&self, base_path: String, extensions: Vec<String>) -> SqlResult<()> {
-        use std::collections::HashSet;
-
-        let image_extensions = ["png", "jpeg", "jpg", "bmp", "tga", "webp", "svg"];
-        let exts_lower: Vec<String> = extensions.iter().map(|e| e.to_lowercase()).collect();
-
-        // A large asset tree can hold tens of thousands of matching files. Opening a fresh
-        // connection per file (and committing each INSERT on its own) made this scan freeze
-        // the editor for minutes. Instead: open one connection, load the existing/deleted
-        // paths into memory once, and insert everything inside a single transaction.
-        let mut conn = self.get_connection()?;
-        let _ = conn.execute_batch("PRAGMA synchronous = OFF; PRAGMA journal_mode = MEMORY;");
-
-        let mut existing: HashSet<String> = HashSet::new();
-        {
-            let mut stmt = conn.prepare("SELECT path FROM individual_assets")?;
-            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
-            for row in rows.flatten() {
-                existing.insert(row);
-            }
-        }
-
-        let mut deleted: HashSet<String> = HashSet::new();
-        {
-            let mut stmt = conn.prepare("SELECT path FROM deleted")?;
-            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
-            for row in rows.flatten() {
-                deleted.insert(row);
-            }
-        }
-
-        let tx = conn.transaction()?;
-        {
-            let mut insert = tx.prepare(
-                "INSERT INTO individual_assets (name, path, image_path, tags) VALUES (?1, ?2, ?3, '[]')",
-            )?;
-
-            for entry in WalkDir::new(&base_path)
-                .follow_links(false)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                let path = entry.path();
-
-                // Skip macOS resource-fork folders (and everything inside them)
-                if path.components().any(|c| c.as_os_str() == "__MACOSX") {
-                    continue;
-                }
-
-                if !path.is_file() {
-                    continue;
-                }
-
-                let ext = match path.extension() {
-                    Some(e) => e.to_string_lossy().to_lowercase(),
-                    None => continue,
-                };
-
-                if !exts_lower.contains(&ext) {
-                    continue;
-                }
-
-                let path_str = path.to_string_lossy().to_string();
-
-                if deleted.contains(&path_str) {
-                    continue;
-                }
-
-                // Skip duplicates (and remember this path so repeats within the walk are skipped too)
-                if !existing.insert(path_str.clone()) {
-                    continue;
-                }
-
-                let name = path
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "Unknown".to_string());
-
-                // An image file is its own preview; other file types have no preview image.
-                let image_path = if image_extensions.contains(&ext.as_str()) {
-                    path_str.clone()
-                } else {
-                    String::new()
-                };
-
-                let _ = insert.execute(params![name, path_str, image_path]);
-            }
-        }
-        tx.commit()?;
-
+    fn remove_individual_asset(&self, id: i64) -> SqlResult<()> {
+        let conn = self.get_connection()?;
+        conn.execute("DELETE FROM individual_assets WHERE id = ?1", params![id])?;
         Ok(())
     }
 
@@ -1296,6 +2325,19 @@ impl AssetManager {
         dict.set("name", asset.name.clone());
         dict.set("path", asset.path.clone());
 
+        if let Some(root_id) = asset.root_id {
+            dict.set("root_id", root_id);
+        }
+
+        if let Some(ref discovered_by) = asset.discovered_by {
+            dict.set("discovered_by", discovered_by.as_str());
+            dict.set("discovered_pattern", asset.discovered_pattern.as_deref().unwrap_or(""));
+        }
+
+        if let Some(ref license) = asset.license {
+            dict.set("license", license.as_str());
+        }
+
         if let Some(ref img_path) = asset.image_path {
             dict.set("image_path", img_path.clone());
         } else {
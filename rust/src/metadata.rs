@@ -0,0 +1,479 @@
+//! Export and import of what was curated in the library (names, previews, tags, import times, the
+//! deleted list and tag aliases and parents) as JSON, to back it up or share it with another machine.
+//!
+//! Paths are written relative to the library root they're under, with the root's label, so they match on a
+//! machine where the library lives somewhere else. Paths outside every root are written as they are.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::MAIN_SEPARATOR_STR;
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+
+use crate::roots::{self, Root};
+use crate::search_key::{natural_key, search_key, split_tokens};
+use crate::tags;
+
+/// Format version written by `export`. Files from a later version are refused.
+pub(crate) const VERSION: i64 = 1;
+
+/// What `import` does when the file and the library disagree. See `AssetManager::import_metadata`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MergePolicy {
+    /// The file's names, previews and tags win. Names and previews the file doesn't have are left alone.
+    Replace,
+    /// The library's values win; the file only fills in what's missing.
+    Keep,
+    /// Like [Replace], but the tags of both are combined.
+    Merge,
+}
+
+impl MergePolicy {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "replace" => Some(MergePolicy::Replace),
+            "keep" => Some(MergePolicy::Keep),
+            "merge" => Some(MergePolicy::Merge),
+            _ => None,
+        }
+    }
+}
+
+/// A pack or individual asset in the file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct AssetEntry {
+    /// Label of the root [path] is relative to, or None if it's a full path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    pub path: String,
+    #[serde(default)]
+    pub name: String,
+    /// Relative to the same root as [path] if it's under it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_path: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imported_at: Option<i64>,
+}
+
+/// A path on the deleted list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct DeletedEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    pub path: String,
+}
+
+/// The exported file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Metadata {
+    pub version: i64,
+    #[serde(default)]
+    pub assets: Vec<AssetEntry>,
+    #[serde(default)]
+    pub individual_assets: Vec<AssetEntry>,
+    #[serde(default)]
+    pub deleted: Vec<DeletedEntry>,
+    /// Alias to the tag it stands for.
+    #[serde(default)]
+    pub tag_aliases: BTreeMap<String, String>,
+    /// Tag to its parent.
+    #[serde(default)]
+    pub tag_parents: BTreeMap<String, String>,
+}
+
+/// What `import` changed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ImportReport {
+    pub assets_updated: i64,
+    pub individual_assets_updated: i64,
+    /// Entries without a matching asset, or with one under more than one root, as `root:path`.
+    pub unmatched: Vec<String>,
+    /// Paths put on the deleted list.
+    pub deleted_added: i64,
+    /// Assets removed because the file has them on the deleted list.
+    pub removed: i64,
+    pub tag_aliases: i64,
+    pub tag_parents: i64,
+}
+
+/// Whether [path] is a full path, on this system or another one.
+fn is_absolute(path: &str) -> bool {
+    path.starts_with(['/', '\\']) || path.as_bytes().get(1) == Some(&b':')
+}
+
+/// [path] relative to [root], if it's under it, with `/` as separator whichever one either of them uses.
+fn strip_root(root: &str, path: &str) -> Option<String> {
+    let (root, path) = (roots::slashed(root), roots::slashed(path));
+    if path == root {
+        return Some(String::new());
+    }
+    path.strip_prefix(&root)?.strip_prefix('/').map(str::to_string)
+}
+
+/// The deepest root [path] is under, and the path relative to it.
+fn relative<'a>(roots: &'a [Root], path: &str) -> Option<(&'a Root, String)> {
+    roots
+        .iter()
+        .filter_map(|root| strip_root(&root.path, path).map(|rest| (root, rest)))
+        .max_by_key(|(root, _)| root.path.len())
+}
+
+/// [path] of an entry under [root_path] as a full path, joined with this system's separator like a scan
+/// joins the folders it walks.
+fn resolve(root_path: &str, path: &str) -> String {
+    if path.is_empty() {
+        return root_path.to_string();
+    }
+    let path = path.replace(['/', '\\'], MAIN_SEPARATOR_STR);
+    let root_path = root_path.strip_suffix(['/', '\\']).unwrap_or(root_path);
+    format!("{}{}{}", root_path, MAIN_SEPARATOR_STR, path)
+}
+
+/// The roots an entry's root label refers to: those with that label, or every root if none has it.
+fn roots_labelled<'a>(roots: &'a [Root], label: &str) -> Vec<&'a Root> {
+    let labelled: Vec<&Root> = roots.iter().filter(|root| root.label == label).collect();
+    if labelled.is_empty() {
+        roots.iter().collect()
+    } else {
+        labelled
+    }
+}
+
+/// A row of [table] as `export` writes it and `import` compares it.
+struct Row {
+    id: i64,
+    path: String,
+    name: String,
+    image_path: Option<String>,
+    tags: Vec<String>,
+    imported_at: Option<i64>,
+}
+
+fn load_rows(conn: &Connection, table: &str) -> SqlResult<Vec<Row>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, path, name, image_path, imported_at,
+            (SELECT json_group_array(g.label ORDER BY l.position) FROM {link} l JOIN tags g ON g.id = l.tag_id
+             WHERE l.asset_id = {t}.id)
+         FROM {t} ORDER BY path",
+        t = table,
+        link = tags::link_table(table)
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        let tags: String = row.get(5)?;
+        Ok(Row {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            name: row.get(2)?,
+            image_path: row.get(3)?,
+            imported_at: row.get(4)?,
+            tags: serde_json::from_str(&tags).unwrap_or_default(),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Everything `import` reads back, with paths made relative to [roots].
+pub(crate) fn export(conn: &Connection) -> SqlResult<Metadata> {
+    let roots = roots::list_roots(conn)?;
+    let entry = |row: Row| {
+        let (root, path) = match relative(&roots, &row.path) {
+            Some((root, path)) => (Some(root), path),
+            None => (None, row.path),
+        };
+        let image_path = row.image_path.filter(|p| !p.is_empty()).map(|image| {
+            root.and_then(|root| strip_root(&root.path, &image).filter(|rest| !rest.is_empty()))
+                .unwrap_or(image)
+        });
+        AssetEntry {
+            root: root.map(|root| root.label.clone()),
+            path,
+            name: row.name,
+            image_path,
+            tags: row.tags,
+            imported_at: row.imported_at,
+        }
+    };
+
+    let assets = load_rows(conn, "assets")?.into_iter().map(entry).collect();
+    let individual_assets = load_rows(conn, "individual_assets")?.into_iter().map(entry).collect();
+
+    let deleted_paths: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT path FROM deleted ORDER BY path")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<SqlResult<_>>()?
+    };
+    let deleted = deleted_paths
+        .into_iter()
+        .map(|path| match relative(&roots, &path) {
+            Some((root, rest)) => DeletedEntry {
+                root: Some(root.label.clone()),
+                path: rest,
+            },
+            None => DeletedEntry { root: None, path },
+        })
+        .collect();
+
+    Ok(Metadata {
+        version: VERSION,
+        assets,
+        individual_assets,
+        deleted,
+        tag_aliases: tags::aliases(conn)?.into_iter().collect(),
+        tag_parents: tags::parents(conn)?.into_iter().collect(),
+    })
+}
+
+/// The rows of one table, by full path and by path relative to their root.
+struct RowIndex<'a> {
+    roots: &'a [Root],
+    rows: Vec<Row>,
+    by_path: HashMap<String, usize>,
+    /// Relative path to (row, root) pairs; the same relative path can be under several roots.
+    by_relative: HashMap<String, Vec<(usize, &'a Root)>>,
+}
+
+impl<'a> RowIndex<'a> {
+    fn load(conn: &Connection, table: &str, roots: &'a [Root]) -> SqlResult<Self> {
+        let rows = load_rows(conn, table)?;
+        let mut by_path = HashMap::new();
+        let mut by_relative: HashMap<String, Vec<(usize, &Root)>> = HashMap::new();
+        for (index, row) in rows.iter().enumerate() {
+            by_path.insert(row.path.clone(), index);
+            if let Some((root, path)) = relative(roots, &row.path) {
+                by_relative.entry(path).or_default().push((index, root));
+            }
+        }
+        Ok(Self {
+            roots,
+            rows,
+            by_path,
+            by_relative,
+        })
+    }
+
+    /// The row [entry] refers to and the root it's under, if exactly one matches.
+    fn find(&self, entry: &AssetEntry) -> Option<(&Row, Option<&'a Root>)> {
+        let Some(label) = &entry.root else {
+            return self.by_path.get(&entry.path).map(|&index| (&self.rows[index], None));
+        };
+        let candidates = self.by_relative.get(&entry.path)?;
+        let labelled = roots_labelled(self.roots, label);
+        let matching: Vec<_> = candidates
+            .iter()
+            .filter(|(_, root)| labelled.iter().any(|r| r.id == root.id))
+            .collect();
+        match matching.as_slice() {
+            [(index, root)] => Some((&self.rows[*index], Some(*root))),
+            _ => None,
+        }
+    }
+}
+
+/// Update [row] of [table] from [entry] as [policy] says. Returns whether anything changed.
+fn apply(
+    conn: &Connection,
+    table: &str,
+    row: &Row,
+    root: Option<&Root>,
+    entry: &AssetEntry,
+    policy: MergePolicy,
+) -> SqlResult<bool> {
+    let file_wins = policy != MergePolicy::Keep;
+
+    let name = if file_wins && !entry.name.is_empty() { &entry.name } else { &row.name };
+
+    let entry_image = entry.image_path.as_deref().filter(|p| !p.is_empty()).map(|image| match root {
+        Some(root) if !is_absolute(image) => resolve(&root.path, image),
+        _ => image.to_string(),
+    });
+    let image_path = match entry_image {
+        Some(image) if file_wins || row.image_path.is_none() => Some(image),
+        _ => row.image_path.clone(),
+    };
+
+    let tags: Vec<String> = match policy {
+        MergePolicy::Replace => entry.tags.clone(),
+        MergePolicy::Merge => row.tags.iter().chain(&entry.tags).cloned().collect(),
+        MergePolicy::Keep if row.tags.is_empty() => entry.tags.clone(),
+        MergePolicy::Keep => row.tags.clone(),
+    };
+
+    // Import history only grows: the later time wins whatever the policy.
+    let imported_at = row.imported_at.max(entry.imported_at);
+
+    let mut changed = false;
+    if *name != row.name {
+        conn.execute(
+            &format!("UPDATE {} SET name = ?1, name_key = ?2, name_tokens = ?3, natural_key = ?4 WHERE id = ?5", table),
+            params![name, search_key(name), split_tokens(name), natural_key(name), row.id],
+        )?;
+        changed = true;
+    }
+    if image_path != row.image_path {
+        // Like a preview picked with `update_asset`, it replaces whatever rule found the old one.
+        let discovered = if table == "assets" { ", discovered_by = 'manual', discovered_pattern = NULL" } else { "" };
+        conn.execute(
+            &format!("UPDATE {} SET image_path = ?1{} WHERE id = ?2", table, discovered),
+            params![image_path, row.id],
+        )?;
+        changed = true;
+    }
+    let normalized = |tags: &[String]| {
+        let mut seen = Vec::new();
+        for tag in tags.iter().map(|t| tags::normalize(t)).filter(|t| !t.is_empty()) {
+            if !seen.contains(&tag) {
+                seen.push(tag);
+            }
+        }
+        seen
+    };
+    if normalized(&tags) != normalized(&row.tags) {
+        tags::set_tags(conn, table, row.id, &tags)?;
+        changed = true;
+    }
+    if imported_at != row.imported_at {
+        conn.execute(&format!("UPDATE {} SET imported_at = ?1 WHERE id = ?2", table), params![imported_at, row.id])?;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// Apply [metadata] to the assets it matches, in one transaction. Entries without a match are reported,
+/// not added: scanning the library adds them, and importing again then applies them.
+pub(crate) fn import(conn: &mut Connection, metadata: &Metadata, policy: MergePolicy) -> SqlResult<ImportReport> {
+    let roots = roots::list_roots(conn)?;
+    let tx = conn.transaction()?;
+    let mut report = ImportReport::default();
+
+    for (table, entries) in [("assets", &metadata.assets), ("individual_assets", &metadata.individual_assets)] {
+        let index = RowIndex::load(&tx, table, &roots)?;
+        let mut updated = 0;
+        for entry in entries {
+            match index.find(entry) {
+                Some((row, root)) => {
+                    if apply(&tx, table, row, root, entry, policy)? {
+                        updated += 1;
+                    }
+                }
+                None => report.unmatched.push(format!("{}:{}", entry.root.as_deref().unwrap_or(""), entry.path)),
+            }
+        }
+        if table == "assets" {
+            report.assets_updated = updated;
+        } else {
+            report.individual_assets_updated = updated;
+        }
+    }
+
+    for entry in &metadata.deleted {
+        let path = match &entry.root {
+            None => entry.path.clone(),
+            Some(label) => match roots_labelled(&roots, label).as_slice() {
+                [root] => resolve(&root.path, &entry.path),
+                _ => {
+                    report.unmatched.push(format!("{}:{}", label, entry.path));
+                    continue;
+                }
+            },
+        };
+
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM assets WHERE path = ?1) OR EXISTS (SELECT 1 FROM individual_assets WHERE path = ?1)",
+            params![path],
+            |row| row.get(0),
+        )?;
+        if exists {
+            // Keeping the library's state means keeping an asset the file has deleted.
+            if policy == MergePolicy::Keep {
+                continue;
+            }
+            report.removed += tx.execute("DELETE FROM assets WHERE path = ?1", params![path])? as i64;
+            report.removed += tx.execute("DELETE FROM individual_assets WHERE path = ?1", params![path])? as i64;
+        }
+        report.deleted_added += tx.execute("INSERT OR IGNORE INTO deleted (path) VALUES (?1)", params![path])? as i64;
+    }
+
+    // Through `set_alias`, so the file's aliases and the library's resolve to the same tags instead of chaining.
+    for (alias, tag) in &metadata.tag_aliases {
+        let existing: Option<String> = tx
+            .query_row("SELECT tag FROM tag_aliases WHERE alias = ?1", params![tags::normalize(alias)], |row| row.get(0))
+            .optional()?;
+        if existing.is_some()
+            && (policy == MergePolicy::Keep || existing == Some(tags::canonical(&tx, &tags::normalize(tag))?))
+        {
+            continue;
+        }
+        if tags::set_alias(&tx, alias, tag)? {
+            report.tag_aliases += 1;
+        }
+    }
+
+    for (tag, parent) in &metadata.tag_parents {
+        let existing: Option<String> = tx
+            .query_row("SELECT parent FROM tag_parents WHERE tag = ?1", params![tags::normalize(tag)], |row| row.get(0))
+            .optional()?;
+        if existing.is_some() && (policy == MergePolicy::Keep || existing == Some(tags::normalize(parent))) {
+            continue;
+        }
+        if tags::set_parent(&tx, tag, parent)? {
+            report.tag_parents += 1;
+        }
+    }
+
+    tx.commit()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    fn aliases_of(metadata: &[(&str, &str)]) -> Metadata {
+        Metadata {
+            version: 1,
+            tag_aliases: metadata.iter().map(|(a, t)| (a.to_string(), t.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn imported_aliases_resolve_to_canonical_tags() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        tags::add_alias(&mut conn, "b", "c").unwrap();
+        tags::add_alias(&mut conn, "x", "a").unwrap();
+
+        // a→b lands on what b stands for, and x, an alias of a, moves along.
+        let report = import(&mut conn, &aliases_of(&[("a", "b")]), MergePolicy::Replace).unwrap();
+        assert_eq!(report.tag_aliases, 1);
+        let expected: Vec<(String, String)> =
+            [("a", "c"), ("b", "c"), ("x", "c")].iter().map(|(a, t)| (a.to_string(), t.to_string())).collect();
+        assert_eq!(tags::aliases(&conn).unwrap(), expected);
+
+        // Importing the same file again changes nothing.
+        let report = import(&mut conn, &aliases_of(&[("a", "b")]), MergePolicy::Replace).unwrap();
+        assert_eq!(report.tag_aliases, 0);
+
+        // Keep leaves existing aliases alone; Replace points them elsewhere.
+        let report = import(&mut conn, &aliases_of(&[("a", "d")]), MergePolicy::Keep).unwrap();
+        assert_eq!(report.tag_aliases, 0);
+        import(&mut conn, &aliases_of(&[("a", "d")]), MergePolicy::Replace).unwrap();
+        assert_eq!(tags::canonical(&conn, "a").unwrap(), "d");
+    }
+
+    #[test]
+    fn root_relative_paths_work_with_either_separator() {
+        assert_eq!(strip_root("C:\\lib", "C:\\lib\\Nature\\Tree.glb").as_deref(), Some("Nature/Tree.glb"));
+        assert_eq!(strip_root("C:/lib", "C:/lib\\Nature\\Tree.glb").as_deref(), Some("Nature/Tree.glb"));
+        assert_eq!(strip_root("/lib", "/lib").as_deref(), Some(""));
+        assert_eq!(strip_root("/lib", "/library/Tree.glb"), None);
+
+        let sep = MAIN_SEPARATOR_STR;
+        assert_eq!(resolve("C:\\lib", "Nature/Tree.glb"), format!("C:\\lib{sep}Nature{sep}Tree.glb"));
+        assert_eq!(resolve("/lib/", "Nature\\Tree.glb"), format!("/lib{sep}Nature{sep}Tree.glb"));
+        assert_eq!(resolve("/lib", ""), "/lib");
+    }
+}
@@ -0,0 +1,392 @@
+//! Schema migrations. Each runs once, in order, inside its own transaction, and `PRAGMA user_version`
+//! records how many have run.
+//!
+//! Databases from before `user_version` was used are at version 0 whatever shape they're in, so the
+//! migrations up to `full_text_indexes` check what's there before changing anything. Later ones can
+//! rely on the schema the earlier ones leave.
+
+use rusqlite::{Connection, Result as SqlResult};
+
+use crate::{search_index, search_key, tags};
+
+type Migration = fn(&Connection) -> SqlResult<()>;
+
+/// Every schema change, oldest first. Only ever append: a database at version N has run the first N.
+const MIGRATIONS: [Migration; 10] = [
+    base_tables,
+    library_roots,
+    asset_json_stamps,
+    discovery_rules,
+    licenses,
+    tag_tables,
+    search_keys,
+    sort_columns,
+    full_text_indexes,
+    kana_voicing_keys,
+];
+
+/// Start of the message of the error `migrate` returns for a database a newer build has migrated further.
+const NEWER_SCHEMA: &str = "Database schema version";
+
+/// Bring the database up to the latest schema, running the migrations it hasn't had yet.
+///
+/// Fails without touching a database at a version past the last migration: its schema may not be
+/// what this build expects (see `is_newer_schema`).
+pub(crate) fn migrate(conn: &mut Connection) -> SqlResult<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() as i64 {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+            Some(format!(
+                "{} {} is newer than the {} this build knows; it was written by a newer version",
+                NEWER_SCHEMA,
+                version,
+                MIGRATIONS.len()
+            )),
+        ));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version.max(0) as usize) {
+        let tx = conn.transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", index as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Whether [error] is `migrate` refusing a database from a newer build.
+pub(crate) fn is_newer_schema(error: &rusqlite::Error) -> bool {
+    matches!(error, rusqlite::Error::SqliteFailure(_, Some(message)) if message.starts_with(NEWER_SCHEMA))
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> SqlResult<bool> {
+    conn.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1", table),
+        [column],
+        |row| row.get(0),
+    )
+}
+
+/// Add [column] to [table] unless it's there already. Returns whether it was added.
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> SqlResult<bool> {
+    if has_column(conn, table, column)? {
+        return Ok(false);
+    }
+    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    Ok(true)
+}
+
+/// Version 1: packs, individual assets and remembered deletions, tags as a JSON column.
+fn base_tables(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS assets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL UNIQUE,
+            image_path TEXT,
+            tags TEXT
+        );
+        CREATE TABLE IF NOT EXISTS deleted (
+            path TEXT PRIMARY KEY
+        );
+        CREATE INDEX IF NOT EXISTS idx_assets_name ON assets(name);
+        CREATE INDEX IF NOT EXISTS idx_assets_path ON assets(path);
+
+        CREATE TABLE IF NOT EXISTS individual_assets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL UNIQUE,
+            image_path TEXT,
+            tags TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_individual_assets_name ON individual_assets(name);
+        CREATE INDEX IF NOT EXISTS idx_individual_assets_path ON individual_assets(path);",
+    )
+}
+
+/// Library roots, and the root each row was found under.
+fn library_roots(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS roots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            path TEXT NOT NULL UNIQUE,
+            label TEXT,
+            enabled INTEGER NOT NULL DEFAULT 1
+        )",
+        [],
+    )?;
+    for table in ["assets", "individual_assets"] {
+        add_column(conn, table, "root_id", "INTEGER REFERENCES roots(id)")?;
+        conn.execute(&format!("CREATE INDEX IF NOT EXISTS idx_{t}_root_id ON {t}(root_id)", t = table), [])?;
+    }
+    Ok(())
+}
+
+/// Asset.json location and (mtime, size) stamp, used to re-ingest edited files on rescan.
+fn asset_json_stamps(conn: &Connection) -> SqlResult<()> {
+    for (column, definition) in [("json_path", "TEXT"), ("json_mtime", "INTEGER"), ("json_size", "INTEGER")] {
+        add_column(conn, "assets", column, definition)?;
+    }
+    Ok(())
+}
+
+/// Which discovery rule produced each pack. Rows from before this stay NULL (unknown).
+fn discovery_rules(conn: &Connection) -> SqlResult<()> {
+    for column in ["discovered_by", "discovered_pattern"] {
+        add_column(conn, "assets", column, "TEXT")?;
+    }
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_assets_discovered_by ON assets(discovered_by)", [])?;
+    Ok(())
+}
+
+/// License from the Asset.json, searchable with license:.
+fn licenses(conn: &Connection) -> SqlResult<()> {
+    add_column(conn, "assets", "license", "TEXT")?;
+    Ok(())
+}
+
+/// Tags moved from the JSON column into the tags and link tables.
+fn tag_tables(conn: &Connection) -> SqlResult<()> {
+    tags::create_tables(conn)?;
+    for (table, _) in tags::TAGGED_TABLES {
+        tags::migrate_json_column(conn, table)?;
+    }
+    Ok(())
+}
+
+/// Unicode search keys for names, paths and tags, used for matching and name order, and the tokens of
+/// compound words in names and paths.
+fn search_keys(conn: &Connection) -> SqlResult<()> {
+    let mut rekeyed = false;
+    for table in ["assets", "individual_assets"] {
+        rekeyed |= search_key::migrate(conn, table)?;
+    }
+    if rekeyed {
+        search_key::migrate_tags(conn)?;
+    }
+    conn.execute_batch(
+        "DROP INDEX IF EXISTS idx_assets_name_nocase;
+        DROP INDEX IF EXISTS idx_individual_assets_name_nocase;
+        CREATE INDEX IF NOT EXISTS idx_assets_name_key ON assets(name_key, name);
+        CREATE INDEX IF NOT EXISTS idx_individual_assets_name_key ON individual_assets(name_key, name);",
+    )
+}
+
+/// When rows were added and imported, and their modification time and size on disk, to sort by.
+/// Rows from before this count as added now, in id order; scans fill in the disk columns.
+fn sort_columns(conn: &Connection) -> SqlResult<()> {
+    for table in ["assets", "individual_assets"] {
+        for column in ["added_at", "imported_at", "disk_mtime", "disk_size"] {
+            if add_column(conn, table, column, "INTEGER")? && column == "added_at" {
+                conn.execute(&format!("UPDATE {} SET added_at = unixepoch()", table), [])?;
+            }
+        }
+        conn.execute(
+            &format!("CREATE INDEX IF NOT EXISTS idx_{t}_natural_key ON {t}(natural_key, name)", t = table),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Full-text search indexes, filled from the existing rows.
+fn full_text_indexes(conn: &Connection) -> SqlResult<()> {
+    for table in search_index::INDEXED_TABLES {
+        search_index::create_fts(conn, table)?;
+    }
+    Ok(())
+}
+
+/// Search keys that keep kana voicing marks, which earlier keys stripped like accents.
+fn kana_voicing_keys(conn: &Connection) -> SqlResult<()> {
+    for table in search_index::INDEXED_TABLES {
+        search_index::drop_indexes(conn, table)?;
+        search_key::rekey(conn, table)?;
+    }
+    search_key::migrate_tags(conn)?;
+    for table in search_index::INDEXED_TABLES {
+        search_index::create_fts(conn, table)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database as the first release left it: tags as JSON, no `user_version`.
+    const V1_SNAPSHOT: &str = r#"
+        CREATE TABLE assets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL UNIQUE,
+            image_path TEXT,
+            tags TEXT
+        );
+        CREATE TABLE deleted (
+            path TEXT PRIMARY KEY
+        );
+        CREATE INDEX idx_assets_name ON assets(name);
+        CREATE INDEX idx_assets_path ON assets(path);
+        CREATE TABLE individual_assets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL UNIQUE,
+            image_path TEXT,
+            tags TEXT
+        );
+        CREATE INDEX idx_individual_assets_name ON individual_assets(name);
+        CREATE INDEX idx_individual_assets_path ON individual_assets(path);
+
+        INSERT INTO assets (id, name, path, image_path, tags) VALUES
+            (1, 'Forest Kit', '/lib/Forest Kit', '/lib/Forest Kit/Preview.png', '["Nature", "3D", "nature"]'),
+            (2, 'Élan UI', '/lib/Elan', NULL, NULL),
+            (5, 'RedTreeLarge', '/lib/RedTreeLarge', '/lib/RedTreeLarge/RedTreeLarge.png', 'not json');
+        INSERT INTO individual_assets (id, name, path, image_path, tags) VALUES
+            (3, 'sfx_door_open.wav', '/lib/Sounds/sfx_door_open.wav', NULL, '["Audio"]');
+        INSERT INTO deleted (path) VALUES ('/lib/Old Pack');
+    "#;
+
+    fn user_version(conn: &Connection) -> i64 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap()
+    }
+
+    fn tags_of(conn: &Connection, link: &str, id: i64) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT t.label FROM {} l JOIN tags t ON t.id = l.tag_id WHERE l.asset_id = ?1 ORDER BY l.position",
+                link
+            ))
+            .unwrap();
+        stmt.query_map([id], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap()
+    }
+
+    fn fts_ids(conn: &Connection, table: &str, query: &str) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT rowid FROM {}_fts WHERE {}_fts MATCH ?1 ORDER BY rowid", table, table))
+            .unwrap();
+        stmt.query_map([query], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap()
+    }
+
+    /// Tables and their columns, in a stable order.
+    fn schema(conn: &Connection) -> Vec<(String, Vec<String>)> {
+        let tables: Vec<String> = {
+            let mut stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+                .unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap()
+        };
+        tables
+            .into_iter()
+            .map(|table| {
+                let mut stmt = conn
+                    .prepare(&format!("SELECT name FROM pragma_table_info('{}') ORDER BY name", table))
+                    .unwrap();
+                let columns = stmt.query_map([], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap();
+                (table, columns)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn upgrades_v1_snapshot_without_losing_data() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(V1_SNAPSHOT).unwrap();
+        assert_eq!(user_version(&conn), 0);
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len() as i64);
+
+        let rows: Vec<(i64, String, String, Option<String>)> = {
+            let mut stmt = conn.prepare("SELECT id, name, path, image_path FROM assets ORDER BY id").unwrap();
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap();
+            rows.collect::<SqlResult<_>>().unwrap()
+        };
+        assert_eq!(
+            rows,
+            vec![
+                (1, "Forest Kit".into(), "/lib/Forest Kit".into(), Some("/lib/Forest Kit/Preview.png".into())),
+                (2, "Élan UI".into(), "/lib/Elan".into(), None),
+                (5, "RedTreeLarge".into(), "/lib/RedTreeLarge".into(), Some("/lib/RedTreeLarge/RedTreeLarge.png".into())),
+            ]
+        );
+        let individual: (String, String) = conn
+            .query_row("SELECT name, path FROM individual_assets WHERE id = 3", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(individual, ("sfx_door_open.wav".into(), "/lib/Sounds/sfx_door_open.wav".into()));
+        let deleted: String = conn.query_row("SELECT path FROM deleted", [], |row| row.get(0)).unwrap();
+        assert_eq!(deleted, "/lib/Old Pack");
+
+        // Tags keep their order and first spelling; repeats, missing and unreadable JSON leave none.
+        assert!(!has_column(&conn, "assets", "tags").unwrap());
+        assert_eq!(tags_of(&conn, "asset_tags", 1), vec!["Nature", "3D"]);
+        assert!(tags_of(&conn, "asset_tags", 2).is_empty());
+        assert!(tags_of(&conn, "asset_tags", 5).is_empty());
+        assert_eq!(tags_of(&conn, "individual_asset_tags", 3), vec!["Audio"]);
+
+        // Keys are filled in for existing rows, and the full-text indexes are built from them.
+        let name_key: String = conn.query_row("SELECT name_key FROM assets WHERE id = 2", [], |row| row.get(0)).unwrap();
+        assert_eq!(name_key, search_key::search_key("Élan UI"));
+        let added: Option<i64> = conn.query_row("SELECT added_at FROM assets WHERE id = 1", [], |row| row.get(0)).unwrap();
+        assert!(added.is_some());
+        assert_eq!(fts_ids(&conn, "assets", "elan"), vec![2]);
+        assert_eq!(fts_ids(&conn, "assets", "nature"), vec![1]);
+        assert_eq!(fts_ids(&conn, "assets", "tree"), vec![5]);
+        assert_eq!(fts_ids(&conn, "individual_assets", "door audio"), vec![3]);
+
+        // The triggers are in place for rows added afterwards.
+        conn.execute("INSERT INTO assets (name, path, name_key, path_key) VALUES ('Rocks', '/lib/Rocks', 'rocks', '/lib/rocks')", [])
+            .unwrap();
+        assert_eq!(fts_ids(&conn, "assets", "rocks").len(), 1);
+    }
+
+    #[test]
+    fn migrations_run_once_and_match_a_new_database() {
+        let mut upgraded = Connection::open_in_memory().unwrap();
+        upgraded.execute_batch(V1_SNAPSHOT).unwrap();
+        migrate(&mut upgraded).unwrap();
+
+        let mut fresh = Connection::open_in_memory().unwrap();
+        migrate(&mut fresh).unwrap();
+        assert_eq!(user_version(&fresh), MIGRATIONS.len() as i64);
+        assert_eq!(schema(&fresh), schema(&upgraded));
+
+        // Already up to date: nothing runs, so the rows and the index stay as they are.
+        let before = schema(&upgraded);
+        migrate(&mut upgraded).unwrap();
+        assert_eq!(schema(&upgraded), before);
+        assert_eq!(fts_ids(&upgraded, "assets", "forest"), vec![1]);
+    }
+
+    #[test]
+    fn refuses_a_database_from_a_newer_build() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        let newer = MIGRATIONS.len() as i64 + 1;
+        conn.pragma_update(None, "user_version", newer).unwrap();
+
+        let error = migrate(&mut conn).unwrap_err();
+        assert!(is_newer_schema(&error), "{}", error);
+        assert!(!crate::recovery::is_corrupt(&error));
+        assert_eq!(user_version(&conn), newer);
+    }
+
+    #[test]
+    fn rekeys_kana_that_lost_their_voicing_marks() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        // What the keys looked like before voicing marks were kept.
+        conn.execute(
+            "INSERT INTO assets (id, name, path, name_key, path_key) VALUES (1, 'がっこう', '/lib/がっこう', 'かっこう', '/lib/かっこう')",
+            [],
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() as i64 - 1).unwrap();
+
+        migrate(&mut conn).unwrap();
+        let name_key: String = conn.query_row("SELECT name_key FROM assets WHERE id = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(name_key, "がっこう");
+        assert_eq!(fts_ids(&conn, "assets", "がっこう"), vec![1]);
+        assert!(fts_ids(&conn, "assets", "かっこう").is_empty());
+    }
+}
@@ -0,0 +1,675 @@
+//! Search query language shared by pack and individual asset search.
+//!
+//! Terms are ANDed: `tree green`. Also supported: `-term` to exclude, `"quoted phrases"`, `a OR b`,
+//! parentheses, and `field:value` qualifiers (the value can be quoted too). `OR` and `AND` must be
+//! upper case; lower case they are plain words. So are words with a colon that isn't a qualifier,
+//! like `C:` or a URL.
+
+use std::fmt;
+
+use crate::scanner::IMAGE_EXTENSIONS;
+use crate::search_key::search_key;
+use crate::tags;
+
+/// A parsed search query.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Query {
+    /// Words looked up in the full-text index.
+    Text(Text),
+    Field(Field, String),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+/// A full-text term: a word matched as a prefix, or a phrase matched exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Text {
+    /// Only look in this index column (`name:`), or in all of them.
+    pub column: Option<&'static str>,
+    pub words: String,
+    pub phrase: bool,
+}
+
+/// Qualifiers that filter on a column instead of the full-text index. `name:` is full-text, see `Text`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Field {
+    /// Substring of the path.
+    Path,
+    /// File extension, without the dot.
+    Ext,
+    /// Root label or id.
+    Root,
+    /// `pack`, or a kind of file (see `KINDS`).
+    Kind,
+    /// Substring of the license from the Asset.json.
+    License,
+    /// Tag name, ignoring case. A trailing `*` matches tags starting with the rest.
+    Tag,
+    /// Discovery rule of a pack.
+    Discovered,
+    /// Preview pattern that discovered a pack.
+    Pattern,
+}
+
+/// Values of `kind:` other than `pack`, with the file extensions they cover.
+pub(crate) const KINDS: [(&str, &[&str]); 3] = [
+    ("image", &IMAGE_EXTENSIONS),
+    ("model", &["obj", "fbx", "glb", "gltf", "blend", "dae"]),
+    ("audio", &["wav", "ogg", "mp3", "flac"]),
+];
+
+/// `kind:` value of packs.
+const KIND_PACK: &str = "pack";
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParseError {
+    pub message: String,
+    /// Character offset in the query where the problem is.
+    pub position: usize,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        Self {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position)
+    }
+}
+
+/// Parse [input]. Returns `Ok(None)` for a query without any terms.
+pub(crate) fn parse(input: &str) -> Result<Option<Query>, ParseError> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: input.chars().count(),
+    };
+    let query = parser.parse_or()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return Err(ParseError::new("Unmatched ')'", token.position));
+    }
+    Ok(Some(query))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Not,
+    Or,
+    And,
+    Word(String),
+    Phrase(String),
+    Field(Field, String),
+    /// `name:`, which searches the full-text index.
+    Name(String, bool),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+enum Qualifier {
+    Name,
+    Field(Field),
+}
+
+fn qualifier(key: &str) -> Option<Qualifier> {
+    Some(Qualifier::Field(match key {
+        "name" => return Some(Qualifier::Name),
+        "path" => Field::Path,
+        "ext" => Field::Ext,
+        "root" => Field::Root,
+        "kind" => Field::Kind,
+        "license" => Field::License,
+        "tag" => Field::Tag,
+        "discovered" => Field::Discovered,
+        "pattern" => Field::Pattern,
+        _ => return None,
+    }))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let read_phrase = |start: usize| -> Result<(String, usize), ParseError> {
+        // [start] is the opening quote.
+        match chars[start + 1..].iter().position(|&c| c == '"') {
+            Some(len) => {
+                let text: String = chars[start + 1..start + 1 + len].iter().collect();
+                if text.trim().is_empty() {
+                    return Err(ParseError::new("Empty phrase", start));
+                }
+                Ok((text, start + len + 2))
+            }
+            None => Err(ParseError::new("Missing closing '\"'", start)),
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let kind = match c {
+            '(' => {
+                i += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                i += 1;
+                TokenKind::RParen
+            }
+            '"' => {
+                let (text, next) = read_phrase(i)?;
+                i = next;
+                TokenKind::Phrase(text)
+            }
+            '-' => {
+                if chars.get(i + 1).is_none_or(|c| c.is_whitespace() || *c == ')') {
+                    return Err(ParseError::new("Nothing to exclude after '-'", i));
+                }
+                i += 1;
+                TokenKind::Not
+            }
+            _ => {
+                while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '(' | ')' | '"') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                let field = word
+                    .split_once(':')
+                    .and_then(|(key, value)| Some((key, value, qualifier(&key.to_lowercase())?)));
+                match field {
+                    Some((key, value, qualifier)) => {
+                        // field:"quoted value"
+                        let (value, phrase) = if value.is_empty() && chars.get(i) == Some(&'"') {
+                            let (text, next) = read_phrase(i)?;
+                            i = next;
+                            (text, true)
+                        } else {
+                            (value.to_string(), false)
+                        };
+                        if value.is_empty() {
+                            return Err(ParseError::new(format!("Missing value after '{}:'", key), start));
+                        }
+
+                        match qualifier {
+                            Qualifier::Field(Field::Kind) => {
+                                let kind = value.to_lowercase();
+                                if kind != KIND_PACK && !KINDS.iter().any(|(name, _)| *name == kind) {
+                                    let names: Vec<&str> =
+                                        std::iter::once(KIND_PACK).chain(KINDS.iter().map(|(name, _)| *name)).collect();
+                                    return Err(ParseError::new(
+                                        format!("Unknown kind '{}', expected one of: {}", value, names.join(", ")),
+                                        start,
+                                    ));
+                                }
+                                TokenKind::Field(Field::Kind, kind)
+                            }
+                            Qualifier::Field(field) => TokenKind::Field(field, value),
+                            Qualifier::Name => TokenKind::Name(value, phrase),
+                        }
+                    }
+                    _ if word == "OR" => TokenKind::Or,
+                    _ if word == "AND" => TokenKind::And,
+                    _ => TokenKind::Word(word),
+                }
+            }
+        };
+
+        tokens.push(Token { kind, position: start });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Length of the query, for errors at the end of it.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |t| t.position)
+    }
+
+    /// `and_expr (OR and_expr)*`
+    fn parse_or(&mut self) -> Result<Query, ParseError> {
+        let mut items = vec![self.parse_and()?];
+        while self.peek() == Some(&TokenKind::Or) {
+            self.pos += 1;
+            items.push(self.parse_and()?);
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Query::Or(items) })
+    }
+
+    /// `unary ([AND] unary)*`
+    fn parse_and(&mut self) -> Result<Query, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(TokenKind::RParen) | Some(TokenKind::Or) => break,
+                Some(TokenKind::And) => {
+                    if items.is_empty() {
+                        return Err(ParseError::new("Missing term before AND", self.position()));
+                    }
+                    self.pos += 1;
+                    if matches!(self.peek(), None | Some(TokenKind::RParen) | Some(TokenKind::Or)) {
+                        return Err(ParseError::new("Missing term after AND", self.position()));
+                    }
+                }
+                Some(_) => items.push(self.parse_unary()?),
+            }
+        }
+
+        match items.len() {
+            0 => Err(ParseError::new(
+                match self.peek() {
+                    Some(TokenKind::Or) if self.pos == 0 => "Missing term before OR",
+                    Some(TokenKind::Or) => "Two ORs in a row",
+                    Some(TokenKind::RParen) if self.pos > 0 && self.tokens[self.pos - 1].kind == TokenKind::LParen => {
+                        "Empty parentheses"
+                    }
+                    Some(TokenKind::RParen) => "Unmatched ')'",
+                    _ if self.pos > 0 && self.tokens[self.pos - 1].kind == TokenKind::LParen => "Missing ')'",
+                    _ => "Missing term after OR",
+                },
+                self.position(),
+            )),
+            1 => Ok(items.remove(0)),
+            _ => Ok(Query::And(items)),
+        }
+    }
+
+    /// `-unary | primary`
+    fn parse_unary(&mut self) -> Result<Query, ParseError> {
+        if self.peek() == Some(&TokenKind::Not) {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    /// `( or_expr ) | field:value | "phrase" | word`
+    fn parse_primary(&mut self) -> Result<Query, ParseError> {
+        let token = self.tokens[self.pos].clone();
+        self.pos += 1;
+
+        let query = match token.kind {
+            TokenKind::LParen => {
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&TokenKind::RParen) {
+                    return Err(ParseError::new("Missing ')'", token.position));
+                }
+                self.pos += 1;
+                inner
+            }
+            TokenKind::Word(words) => Query::Text(Text {
+                column: None,
+                words,
+                phrase: false,
+            }),
+            TokenKind::Phrase(words) => Query::Text(Text {
+                column: None,
+                words,
+                phrase: true,
+            }),
+            TokenKind::Name(words, phrase) => Query::Text(Text {
+                column: Some("name"),
+                words,
+                phrase,
+            }),
+            TokenKind::Field(field, value) => Query::Field(field, value),
+            // parse_and and parse_unary handle the rest before getting here.
+            TokenKind::RParen | TokenKind::Not | TokenKind::Or | TokenKind::And => {
+                return Err(ParseError::new("Unexpected token", token.position));
+            }
+        };
+
+        // A term of only punctuation would silently match nothing.
+        if let Query::Text(text) = &query {
+            if text.fts_expression().is_none() {
+                return Err(ParseError::new(format!("Nothing to search for in '{}'", text.words), token.position));
+            }
+        }
+        Ok(query)
+    }
+}
+
+/// The table a query runs against, and which columns it has.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SearchTarget {
+    pub table: &'static str,
+    pub is_packs: bool,
+}
+
+pub(crate) const PACKS: SearchTarget = SearchTarget {
+    table: "assets",
+    is_packs: true,
+};
+
+pub(crate) const INDIVIDUAL: SearchTarget = SearchTarget {
+    table: "individual_assets",
+    is_packs: false,
+};
+
+/// Escape [value] for `LIKE ... ESCAPE '\'`.
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+impl Text {
+    /// FTS5 expression for this term, or None if it has no words the index could match.
+    ///
+    /// Quoting makes FTS5 syntax in user input (`*`, `:`, `^`, `NEAR`) match literally.
+    pub fn fts_expression(&self) -> Option<String> {
+        // The index holds search keys, so the words have to be keys too.
+        let words = search_key(&self.words);
+        if !words.chars().any(char::is_alphanumeric) {
+            return None;
+        }
+        let quoted = format!("\"{}\"", words.replace('"', "\"\""));
+        let term = if self.phrase { quoted } else { format!("{}*", quoted) };
+        Some(match self.column {
+            Some(column) => format!("{} : {}", column, term),
+            None => term,
+        })
+    }
+}
+
+impl Query {
+    /// SQL condition on [target]'s table for this query. Values are appended to [params] and
+    /// referenced by number, so [params] may already hold earlier parameters.
+    pub fn to_sql(&self, target: &SearchTarget, params: &mut Vec<String>) -> String {
+        self.compile(target, params, false)
+    }
+
+    /// Like `to_sql`, but full-text terms that aren't excluded match every row, leaving them to fuzzy matching.
+    pub fn to_sql_without_text(&self, target: &SearchTarget, params: &mut Vec<String>) -> String {
+        self.compile(target, params, true)
+    }
+
+    fn compile(&self, target: &SearchTarget, params: &mut Vec<String>, skip_text: bool) -> String {
+        let t = target.table;
+        let mut param = |value: String| {
+            params.push(value);
+            params.len()
+        };
+
+        match self {
+            Query::Text(_) if skip_text => "1".to_string(),
+            Query::Text(text) => match text.fts_expression() {
+                Some(expression) => format!(
+                    "{t}.id IN (SELECT rowid FROM {t}_fts WHERE {t}_fts MATCH ?{})",
+                    param(expression),
+                    t = t
+                ),
+                None => "0".to_string(),
+            },
+            Query::Field(field, value) => match field {
+                Field::Path => format!(
+                    "path_key LIKE ?{} ESCAPE '\\'",
+                    param(format!("%{}%", escape_like(&search_key(value))))
+                ),
+                Field::Ext => format!(
+                    "path_key LIKE ?{} ESCAPE '\\'",
+                    param(format!("%.{}", escape_like(&search_key(value.trim_start_matches('.')))))
+                ),
+                Field::Root => {
+                    let idx = param(value.clone());
+                    format!(
+                        "root_id IN (SELECT id FROM roots WHERE label = ?{} COLLATE NOCASE OR CAST(id AS TEXT) = ?{})",
+                        idx, idx
+                    )
+                }
+                Field::Kind => {
+                    if value == KIND_PACK {
+                        return if target.is_packs { "1" } else { "0" }.to_string();
+                    }
+                    if target.is_packs {
+                        return "0".to_string();
+                    }
+                    let extensions = KINDS.iter().find(|(name, _)| name == value).map_or(&[][..], |(_, e)| *e);
+                    let conditions: Vec<String> =
+                        extensions.iter().map(|ext| format!("path_key LIKE '%.{}'", ext)).collect();
+                    if conditions.is_empty() {
+                        "0".to_string()
+                    } else {
+                        format!("({})", conditions.join(" OR "))
+                    }
+                }
+                Field::License if target.is_packs => {
+                    format!("license LIKE ?{} ESCAPE '\\'", param(format!("%{}%", escape_like(value))))
+                }
+                Field::Tag => match value.strip_suffix('*') {
+                    Some(prefix) => tags::condition(t, prefix, true, params),
+                    None => tags::condition(t, value, false, params),
+                },
+                Field::Discovered if target.is_packs => {
+                    format!("discovered_by = ?{} COLLATE NOCASE", param(value.clone()))
+                }
+                Field::Pattern if target.is_packs => format!("discovered_pattern = ?{}", param(value.clone())),
+                // Individual assets have no Asset.json and aren't discovered by rules.
+                Field::License | Field::Discovered | Field::Pattern => "0".to_string(),
+            },
+            // NULL columns make a comparison NULL; excluding them should still keep the row.
+            Query::Not(inner) => format!("NOT coalesce({}, 0)", inner.compile(target, params, false)),
+            Query::And(items) => {
+                let parts: Vec<String> = items.iter().map(|q| q.compile(target, params, skip_text)).collect();
+                format!("({})", parts.join(" AND "))
+            }
+            Query::Or(items) => {
+                let parts: Vec<String> = items.iter().map(|q| q.compile(target, params, skip_text)).collect();
+                format!("({})", parts.join(" OR "))
+            }
+        }
+    }
+
+    /// FTS5 expression to rank results with: every full-text term that isn't excluded, ORed.
+    pub fn rank_expression(&self) -> Option<String> {
+        let mut terms = Vec::new();
+        self.collect_rank_terms(&mut terms);
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" OR "))
+        }
+    }
+
+    /// Words of the full-text terms that aren't excluded, for fuzzy matching.
+    pub fn fuzzy_words(&self) -> Vec<String> {
+        match self {
+            Query::Text(text) => search_key(&text.words)
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Query::And(items) | Query::Or(items) => items.iter().flat_map(Query::fuzzy_words).collect(),
+            Query::Field(..) | Query::Not(_) => Vec::new(),
+        }
+    }
+
+    fn collect_rank_terms(&self, terms: &mut Vec<String>) {
+        match self {
+            Query::Text(text) => terms.extend(text.fts_expression()),
+            Query::And(items) | Query::Or(items) => {
+                for item in items {
+                    item.collect_rank_terms(terms);
+                }
+            }
+            Query::Field(..) | Query::Not(_) => {}
+        }
+    }
+}
+
+/// Why a search failed: the query didn't parse, the cursor to continue from wasn't made for this
+/// order, or the database couldn't run it.
+#[derive(Debug)]
+pub(crate) enum SearchError {
+    Parse(ParseError),
+    InvalidCursor,
+    Sql(rusqlite::Error),
+}
+
+impl From<ParseError> for SearchError {
+    fn from(e: ParseError) -> Self {
+        SearchError::Parse(e)
+    }
+}
+
+impl From<rusqlite::Error> for SearchError {
+    fn from(e: rusqlite::Error) -> Self {
+        SearchError::Sql(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(words: &str) -> Query {
+        Query::Text(Text {
+            column: None,
+            words: words.to_string(),
+            phrase: false,
+        })
+    }
+
+    fn phrase(words: &str) -> Query {
+        Query::Text(Text {
+            column: None,
+            words: words.to_string(),
+            phrase: true,
+        })
+    }
+
+    fn not(query: Query) -> Query {
+        Query::Not(Box::new(query))
+    }
+
+    fn parsed(input: &str) -> Query {
+        parse(input).unwrap().unwrap()
+    }
+
+    fn error(input: &str) -> (String, usize) {
+        let e = parse(input).unwrap_err();
+        (e.message, e.position)
+    }
+
+    #[test]
+    fn terms_are_anded_and_or_binds_loosest() {
+        assert_eq!(parsed("tree green"), Query::And(vec![word("tree"), word("green")]));
+        assert_eq!(parsed("a AND b"), Query::And(vec![word("a"), word("b")]));
+        assert_eq!(parsed("a OR b c"), Query::Or(vec![word("a"), Query::And(vec![word("b"), word("c")])]));
+        assert_eq!(parsed("a b OR c"), Query::Or(vec![Query::And(vec![word("a"), word("b")]), word("c")]));
+        assert_eq!(parsed("a OR b OR c"), Query::Or(vec![word("a"), word("b"), word("c")]));
+        // Only upper case OR and AND are operators.
+        assert_eq!(parsed("a or and"), Query::And(vec![word("a"), word("or"), word("and")]));
+    }
+
+    #[test]
+    fn negation_phrases_and_parentheses() {
+        assert_eq!(parsed("-tree"), not(word("tree")));
+        assert_eq!(parsed("--tree"), not(not(word("tree"))));
+        assert_eq!(parsed("tree -\"dead wood\""), Query::And(vec![word("tree"), not(phrase("dead wood"))]));
+        assert_eq!(parsed("(a OR b) c"), Query::And(vec![Query::Or(vec![word("a"), word("b")]), word("c")]));
+        assert_eq!(parsed("-(a OR b)"), not(Query::Or(vec![word("a"), word("b")])));
+        assert_eq!(parsed("((a))"), word("a"));
+        // Dashes inside a word don't exclude.
+        assert_eq!(parsed("sci-fi"), word("sci-fi"));
+    }
+
+    #[test]
+    fn qualifiers() {
+        assert_eq!(parsed("tag:Nature"), Query::Field(Field::Tag, "Nature".into()));
+        assert_eq!(parsed("TAG:tree*"), Query::Field(Field::Tag, "tree*".into()));
+        assert_eq!(parsed("path:\"My Folder\""), Query::Field(Field::Path, "My Folder".into()));
+        assert_eq!(parsed("kind:Image"), Query::Field(Field::Kind, "image".into()));
+        assert_eq!(parsed("-ext:png"), not(Query::Field(Field::Ext, "png".into())));
+        assert_eq!(
+            parsed("name:\"red tree\""),
+            Query::Text(Text {
+                column: Some("name"),
+                words: "red tree".into(),
+                phrase: true,
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_qualifiers_are_plain_words() {
+        assert_eq!(parsed("http://example.com/tree"), word("http://example.com/tree"));
+        assert_eq!(parsed("C:"), word("C:"));
+        assert_eq!(parsed("C:\\Assets tree"), Query::And(vec![word("C:\\Assets"), word("tree")]));
+        assert_eq!(parsed("12:30"), word("12:30"));
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(parse("").unwrap(), None);
+        assert_eq!(parse("   ").unwrap(), None);
+
+        assert_eq!(error("tree \"dead"), ("Missing closing '\"'".into(), 5));
+        assert_eq!(error("\"  \""), ("Empty phrase".into(), 0));
+        assert_eq!(error("tree - wood"), ("Nothing to exclude after '-'".into(), 5));
+        assert_eq!(error("tag:"), ("Missing value after 'tag:'".into(), 0));
+        assert_eq!(error("kind:mesh").0, "Unknown kind 'mesh', expected one of: pack, image, model, audio");
+        assert_eq!(error("(a"), ("Missing ')'".into(), 0));
+        assert_eq!(error("a)"), ("Unmatched ')'".into(), 1));
+        assert_eq!(error("()"), ("Empty parentheses".into(), 1));
+        assert_eq!(error("OR a"), ("Missing term before OR".into(), 0));
+        assert_eq!(error("a OR OR b"), ("Two ORs in a row".into(), 5));
+        assert_eq!(error("a OR"), ("Missing term after OR".into(), 4));
+        assert_eq!(error("AND a"), ("Missing term before AND".into(), 0));
+        assert_eq!(error("a AND"), ("Missing term after AND".into(), 5));
+        assert_eq!(error("tree !!!"), ("Nothing to search for in '!!!'".into(), 5));
+        assert_eq!(error("\"-\""), ("Nothing to search for in '-'".into(), 0));
+        assert_eq!(error("name:*"), ("Nothing to search for in '*'".into(), 0));
+    }
+
+    #[test]
+    fn compiles_to_sql_with_numbered_params() {
+        let mut params = vec!["earlier".to_string()];
+        let sql = parsed("tree -ext:.PNG").to_sql(&INDIVIDUAL, &mut params);
+        assert_eq!(
+            sql,
+            "(individual_assets.id IN (SELECT rowid FROM individual_assets_fts WHERE individual_assets_fts MATCH ?2) \
+             AND NOT coalesce(path_key LIKE ?3 ESCAPE '\\', 0))"
+        );
+        assert_eq!(params, vec!["earlier", "\"tree\"*", "%.png"]);
+
+        let mut params = Vec::new();
+        assert_eq!(parsed("kind:pack").to_sql(&INDIVIDUAL, &mut params), "0");
+        assert_eq!(parsed("kind:pack").to_sql(&PACKS, &mut params), "1");
+        assert_eq!(parsed("tree").to_sql_without_text(&PACKS, &mut params), "1");
+        assert!(params.is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+//! Checking the database file, and rebuilding it when it's damaged.
+//!
+//! Most of the database can be found again on disk, so a damaged file is moved aside and a new one is
+//! filled by scanning the library roots again. What can't be found on disk (the roots themselves, the
+//! deleted list, tag aliases and parents) is carried over by `restore` as far as it can still be read from
+//! the old file; the scan itself runs as a `ScanJob`. Edits made with `update_asset` that aren't in an
+//! Asset.json, and import times, are lost.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, ErrorCode, Result as SqlResult};
+
+use crate::roots::{self, Root};
+use crate::scanner;
+
+/// Whether [error] means the file isn't a usable database, as opposed to a busy or missing one.
+pub(crate) fn is_corrupt(error: &rusqlite::Error) -> bool {
+    matches!(error.sqlite_error_code(), Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase))
+}
+
+/// Whether [error] means another connection or process holds the database.
+pub(crate) fn is_busy(error: &rusqlite::Error) -> bool {
+    matches!(error.sqlite_error_code(), Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked))
+}
+
+/// A row that refers to a row that doesn't exist, from `PRAGMA foreign_key_check`.
+#[derive(Debug, Clone)]
+pub(crate) struct ForeignKeyError {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+}
+
+/// What `check_integrity` found. Both lists are empty for a healthy database.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IntegrityReport {
+    /// Problems reported by `PRAGMA integrity_check`: damaged pages, broken indexes and the like.
+    pub errors: Vec<String>,
+    /// Dangling references, e.g. to a removed root. They don't make the file damaged: the rows are still
+    /// readable, and rebuilding would lose more than the reference.
+    pub foreign_key_errors: Vec<ForeignKeyError>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        !self.is_damaged() && self.foreign_key_errors.is_empty()
+    }
+
+    /// Whether the file itself is damaged, so only a rebuild can fix it.
+    pub fn is_damaged(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
+
+/// The problems `PRAGMA integrity_check` reports.
+fn integrity_errors(conn: &Connection) -> SqlResult<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    Ok(rows.collect::<SqlResult<Vec<_>>>()?.into_iter().filter(|line| line != "ok").collect())
+}
+
+/// Run SQLite's integrity and foreign key checks. Reads the whole file, so it takes a while on large libraries.
+pub(crate) fn check_integrity(conn: &Connection) -> SqlResult<IntegrityReport> {
+    let errors = integrity_errors(conn)?;
+
+    let foreign_key_errors = {
+        let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(ForeignKeyError {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+            })
+        })?;
+        rows.collect::<SqlResult<_>>()?
+    };
+
+    Ok(IntegrityReport {
+        errors,
+        foreign_key_errors,
+    })
+}
+
+/// Whether the database at [path] needs rebuilding: the integrity check found a problem, or the file can't
+/// be read as a database at all. A missing file isn't damaged, and a busy one is an error, not damage.
+/// Neither are dangling references (see `IntegrityReport::foreign_key_errors`).
+pub(crate) fn is_damaged(path: &str) -> SqlResult<bool> {
+    if !Path::new(path).is_file() {
+        return Ok(false);
+    }
+    match Connection::open(path).and_then(|conn| integrity_errors(&conn)) {
+        Ok(errors) => Ok(!errors.is_empty()),
+        Err(e) if is_corrupt(&e) => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+/// What could still be read from a damaged database.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Salvage {
+    pub roots: Vec<Root>,
+    pub deleted: Vec<String>,
+    /// (alias, tag) pairs.
+    pub tag_aliases: Vec<(String, String)>,
+    /// (tag, parent) pairs.
+    pub tag_parents: Vec<(String, String)>,
+    /// Rows the old file had, if it could count them.
+    pub asset_count: Option<i64>,
+    pub individual_asset_count: Option<i64>,
+    /// What couldn't be read, and why.
+    pub errors: Vec<String>,
+}
+
+/// Read what `restore` carries over from the database at [path], as far as it can be read.
+pub(crate) fn salvage(path: &str) -> Salvage {
+    let mut salvage = Salvage::default();
+    let conn = match Connection::open(path) {
+        Ok(conn) => conn,
+        Err(e) => {
+            salvage.errors.push(e.to_string());
+            return salvage;
+        }
+    };
+
+    match roots::list_roots(&conn) {
+        Ok(roots) => salvage.roots = roots,
+        Err(e) => salvage.errors.push(format!("roots: {}", e)),
+    }
+
+    let deleted = conn.prepare("SELECT path FROM deleted").and_then(|mut stmt| {
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<SqlResult<Vec<_>>>()
+    });
+    match deleted {
+        Ok(deleted) => salvage.deleted = deleted,
+        Err(e) => salvage.errors.push(format!("deleted: {}", e)),
+    }
+
+    let pairs = |table: &str, columns: &str| {
+        conn.prepare(&format!("SELECT {} FROM {}", columns, table)).and_then(|mut stmt| {
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+            rows.collect::<SqlResult<Vec<_>>>()
+        })
+    };
+    match pairs("tag_aliases", "alias, tag") {
+        Ok(aliases) => salvage.tag_aliases = aliases,
+        Err(e) => salvage.errors.push(format!("tag_aliases: {}", e)),
+    }
+    match pairs("tag_parents", "tag, parent") {
+        Ok(parents) => salvage.tag_parents = parents,
+        Err(e) => salvage.errors.push(format!("tag_parents: {}", e)),
+    }
+
+    let count = |table: &str| conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get::<_, i64>(0));
+    salvage.asset_count = count("assets").ok();
+    salvage.individual_asset_count = count("individual_assets").ok();
+    salvage
+}
+
+/// Move the database at [path] aside, with its write-ahead log, as `<path>.corrupt-<unix time>`.
+/// Returns the new path, or None if there was no file to move.
+///
+/// Every connection to it must be closed first.
+pub(crate) fn back_up(path: &str) -> std::io::Result<Option<String>> {
+    if !Path::new(path).is_file() {
+        return Ok(None);
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| t.as_secs());
+    let backup = format!("{}.corrupt-{}", path, now);
+    std::fs::rename(path, &backup)?;
+    // The log holds the latest commits, so it stays with the file it belongs to.
+    for suffix in ["-wal", "-shm"] {
+        let file = format!("{}{}", path, suffix);
+        if Path::new(&file).is_file() {
+            std::fs::rename(&file, format!("{}{}", backup, suffix))?;
+        }
+    }
+    Ok(Some(backup))
+}
+
+/// What `restore` put back.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Recovery {
+    pub roots_restored: i64,
+    pub deleted_restored: i64,
+    /// Tag aliases and parents put back.
+    pub tag_links_restored: i64,
+}
+
+/// Put back what [salvage] read from the old file into a new database: the roots, keeping their ids, the
+/// deleted list and the tag aliases and parents. Scanning the enabled roots (see `roots::scan_all_roots`)
+/// then finds the assets again.
+///
+/// [conn] must already have the current schema.
+pub(crate) fn restore(conn: &mut Connection, salvage: &Salvage) -> SqlResult<Recovery> {
+    let mut recovery = Recovery::default();
+
+    let tx = conn.transaction()?;
+    for root in &salvage.roots {
+        recovery.roots_restored += tx.execute(
+            "INSERT OR IGNORE INTO roots (id, path, label, enabled) VALUES (?1, ?2, ?3, ?4)",
+            params![root.id, root.path, root.label, root.enabled],
+        )? as i64;
+    }
+    for path in &salvage.deleted {
+        recovery.deleted_restored += tx.execute("INSERT OR IGNORE INTO deleted (path) VALUES (?1)", params![path])? as i64;
+    }
+    for (alias, tag) in &salvage.tag_aliases {
+        recovery.tag_links_restored +=
+            tx.execute("INSERT OR IGNORE INTO tag_aliases (alias, tag) VALUES (?1, ?2)", params![alias, tag])? as i64;
+    }
+    for (tag, parent) in &salvage.tag_parents {
+        recovery.tag_links_restored +=
+            tx.execute("INSERT OR IGNORE INTO tag_parents (tag, parent) VALUES (?1, ?2)", params![tag, parent])? as i64;
+    }
+    tx.commit()?;
+    Ok(recovery)
+}
+
+/// How many packs the database has, how many of those were read from an Asset.json, and how many
+/// individual assets, to report what a rebuild found.
+pub(crate) fn recovered_counts(conn: &Connection) -> SqlResult<(i64, i64, i64)> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM assets),
+                (SELECT COUNT(*) FROM assets WHERE discovered_by = ?1),
+                (SELECT COUNT(*) FROM individual_assets)",
+        params![scanner::DISCOVERED_BY_ASSET_JSON],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    #[test]
+    fn only_damaged_files_need_a_rebuild() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("local_assets.db");
+        let path = path.to_str().unwrap();
+        assert!(!is_damaged(path).unwrap());
+
+        let mut conn = Connection::open(path).unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        drop(conn);
+        assert!(!is_damaged(path).unwrap());
+
+        std::fs::write(path, vec![0x5a; 8192]).unwrap();
+        assert!(is_damaged(path).unwrap());
+    }
+
+    #[test]
+    fn dangling_references_are_reported_but_not_damage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("local_assets.db");
+        let path = path.to_str().unwrap();
+
+        let mut conn = Connection::open(path).unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        // A root removed while its rows still pointed at it.
+        conn.pragma_update(None, "foreign_keys", false).unwrap();
+        conn.execute("INSERT INTO assets (name, path, root_id) VALUES ('Forest', '/lib/Forest', 99)", []).unwrap();
+
+        let report = check_integrity(&conn).unwrap();
+        assert!(!report.is_ok());
+        assert!(!report.is_damaged());
+        assert_eq!(report.foreign_key_errors.len(), 1);
+        assert_eq!(report.foreign_key_errors[0].parent, "roots");
+        drop(conn);
+        assert!(!is_damaged(path).unwrap());
+    }
+
+    #[test]
+    fn restore_carries_over_what_was_salvaged() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.db");
+        let old = old.to_str().unwrap();
+        let mut conn = Connection::open(old).unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        let root = roots::add_root(&conn, "/lib", "Library").unwrap();
+        conn.execute_batch(
+            "INSERT INTO deleted (path) VALUES ('/lib/Old');
+             INSERT INTO tag_aliases (alias, tag) VALUES ('sfx', 'audio');
+             INSERT INTO tag_parents (tag, parent) VALUES ('ambience', 'audio');
+             INSERT INTO assets (name, path) VALUES ('Forest', '/lib/Forest');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let salvage = salvage(old);
+        assert!(salvage.errors.is_empty(), "{:?}", salvage.errors);
+        assert_eq!(salvage.asset_count, Some(1));
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        let recovery = restore(&mut conn, &salvage).unwrap();
+        assert_eq!((recovery.roots_restored, recovery.deleted_restored, recovery.tag_links_restored), (1, 1, 2));
+        let roots = roots::list_roots(&conn).unwrap();
+        assert_eq!((roots[0].id, roots[0].path.as_str()), (root, "/lib"));
+        assert_eq!(recovered_counts(&conn).unwrap(), (0, 0, 0));
+    }
+}
@@ -0,0 +1,239 @@
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
+
+use crate::scanner::{self, CancelToken, ScanOptions, ScanSummary};
+
+/// A library folder registered with `add_root`. Every asset found under it is linked to it.
+#[derive(Debug, Clone)]
+pub(crate) struct Root {
+    pub id: i64,
+    pub path: String,
+    pub label: String,
+    pub enabled: bool,
+}
+
+/// Strip trailing separators so "a/b/" and "a/b" are the same root.
+fn normalize(path: &str) -> &str {
+    let trimmed = path.trim_end_matches(['/', '\\']);
+    if trimmed.is_empty() { path } else { trimmed }
+}
+
+/// SQL condition: [column] is the folder in parameter [param] or anything below it. `/` and `\` are the
+/// same separator, since WalkDir joins with `\` on Windows whatever the root was written with. Bind the
+/// folder through `slashed`.
+pub(crate) fn under(column: &str, param: usize) -> String {
+    format!(
+        "(replace({c}, '\\', '/') = ?{p} OR substr(replace({c}, '\\', '/'), 1, length(?{p}) + 1) = ?{p} || '/')",
+        c = column,
+        p = param
+    )
+}
+
+/// [path] with `/` as its only separator, as `under` compares it.
+pub(crate) fn slashed(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// Register [path] as a root, or update the label of the existing one. Returns the root id.
+pub(crate) fn add_root(conn: &Connection, path: &str, label: &str) -> SqlResult<i64> {
+    let path = normalize(path);
+
+    let existing: Option<i64> = conn
+        .query_row("SELECT id FROM roots WHERE path = ?1", params![path], |row| row.get(0))
+        .optional()?;
+
+    let id = match existing {
+        Some(id) => {
+            if !label.is_empty() {
+                conn.execute("UPDATE roots SET label = ?1 WHERE id = ?2", params![label, id])?;
+            }
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO roots (path, label, enabled) VALUES (?1, ?2, 1)",
+                params![path, label],
+            )?;
+            let id = conn.last_insert_rowid();
+            // Rows under the new root may already be linked to a root above it, and it is now the deeper one.
+            conn.execute(
+                &format!("UPDATE assets SET root_id = NULL WHERE {} OR {}", under("path", 1), under("json_path", 1)),
+                params![slashed(path)],
+            )?;
+            conn.execute(
+                &format!("UPDATE individual_assets SET root_id = NULL WHERE {}", under("path", 1)),
+                params![slashed(path)],
+            )?;
+            id
+        }
+    };
+
+    // Claim anything indexed under this folder before it was a root.
+    link_to_roots(conn)?;
+    Ok(id)
+}
+
+/// Remove a root and every asset linked to it. Returns false if there was no such root.
+pub(crate) fn remove_root(conn: &mut Connection, id: i64) -> SqlResult<bool> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM assets WHERE root_id = ?1", params![id])?;
+    tx.execute("DELETE FROM individual_assets WHERE root_id = ?1", params![id])?;
+    let removed = tx.execute("DELETE FROM roots WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(removed > 0)
+}
+
+pub(crate) fn set_root_enabled(conn: &Connection, id: i64, enabled: bool) -> SqlResult<bool> {
+    let updated = conn.execute("UPDATE roots SET enabled = ?1 WHERE id = ?2", params![enabled, id])?;
+    Ok(updated > 0)
+}
+
+pub(crate) fn list_roots(conn: &Connection) -> SqlResult<Vec<Root>> {
+    let mut stmt = conn.prepare("SELECT id, path, label, enabled FROM roots ORDER BY label COLLATE NOCASE, path")?;
+    let roots = stmt.query_map([], |row| {
+        Ok(Root {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            label: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            enabled: row.get(3)?,
+        })
+    })?;
+    roots.collect()
+}
+
+/// Number of packs and individual assets linked to a root.
+pub(crate) fn root_counts(conn: &Connection, id: i64) -> SqlResult<(i64, i64)> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM assets WHERE root_id = ?1),
+                (SELECT COUNT(*) FROM individual_assets WHERE root_id = ?1)",
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Link every unlinked row to the deepest root it lives under.
+///
+/// Packs defined by an Asset.json can point their path elsewhere, so those also match on
+/// where the Asset.json itself lives.
+pub(crate) fn link_to_roots(conn: &Connection) -> SqlResult<()> {
+    let roots: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT id, path FROM roots ORDER BY length(path) DESC")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<SqlResult<_>>()?
+    };
+
+    for (id, path) in &roots {
+        conn.execute(
+            &format!(
+                "UPDATE assets SET root_id = ?1 WHERE root_id IS NULL AND ({} OR {})",
+                under("path", 2),
+                under("json_path", 2)
+            ),
+            params![id, slashed(path)],
+        )?;
+        conn.execute(
+            &format!("UPDATE individual_assets SET root_id = ?1 WHERE root_id IS NULL AND {}", under("path", 2)),
+            params![id, slashed(path)],
+        )?;
+    }
+    Ok(())
+}
+
+/// Scan every enabled root for packs and, if [extensions] is not empty, individual assets.
+///
+/// All roots are scanned in one transaction, so a cancelled scan keeps or rolls back every root together.
+pub(crate) fn scan_all_roots(
+    conn: &mut Connection,
+    options: &ScanOptions,
+    extensions: &[String],
+    cancel: &CancelToken,
+    on_progress: &mut dyn FnMut(&ScanSummary),
+) -> SqlResult<ScanSummary> {
+    let roots: Vec<Root> = list_roots(conn)?.into_iter().filter(|r| r.enabled).collect();
+    let mut total = ScanSummary::default();
+
+    let tx = conn.transaction()?;
+    for root in &roots {
+        if cancel.is_cancelled() {
+            break;
+        }
+
+        let packs = scanner::scan_directory_in(&tx, &root.path, options, cancel, &mut offset_progress(&total, on_progress))?;
+        total.merge(&packs);
+
+        if !extensions.is_empty() && !cancel.is_cancelled() {
+            let individual = scanner::scan_individual_directory_in(
+                &tx,
+                &root.path,
+                extensions,
+                options,
+                cancel,
+                &mut offset_progress(&total, on_progress),
+            )?;
+            total.merge(&individual);
+        }
+    }
+
+    scanner::finish_transaction(tx, &mut total, cancel)?;
+    Ok(total)
+}
+
+/// Report progress of one scan on top of the totals of the scans before it.
+fn offset_progress<'a>(
+    done: &ScanSummary,
+    on_progress: &'a mut dyn FnMut(&ScanSummary),
+) -> impl FnMut(&ScanSummary) + 'a {
+    let done = done.clone();
+    move |current| {
+        let mut running = done.clone();
+        running.merge(current);
+        on_progress(&running);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    #[test]
+    fn deeper_root_takes_over_linked_rows() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO assets (name, path) VALUES ('Forest', '/lib/kits/Forest'), ('Rocks', '/lib/Rocks');
+             INSERT INTO individual_assets (name, path) VALUES ('tree.glb', '/lib/kits/tree.glb');",
+        )
+        .unwrap();
+
+        let outer = add_root(&conn, "/lib", "").unwrap();
+        let inner = add_root(&conn, "/lib/kits/", "").unwrap();
+
+        let root_of = |table: &str, path: &str| -> i64 {
+            conn.query_row(&format!("SELECT root_id FROM {} WHERE path = ?1", table), params![path], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(root_of("assets", "/lib/kits/Forest"), inner);
+        assert_eq!(root_of("individual_assets", "/lib/kits/tree.glb"), inner);
+        assert_eq!(root_of("assets", "/lib/Rocks"), outer);
+    }
+
+    #[test]
+    fn windows_separators_link_to_their_root() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            r"INSERT INTO assets (name, path) VALUES ('Forest', 'C:/lib\kits\Forest'), ('Other', 'C:/library\Other');
+              INSERT INTO individual_assets (name, path) VALUES ('tree.glb', 'C:\lib\tree.glb');",
+        )
+        .unwrap();
+
+        let lib = add_root(&conn, "C:/lib", "").unwrap();
+        let kits = add_root(&conn, r"C:\lib\kits\", "").unwrap();
+
+        assert_eq!(root_counts(&conn, lib).unwrap(), (0, 1));
+        assert_eq!(root_counts(&conn, kits).unwrap(), (1, 0));
+        assert!(remove_root(&mut conn, kits).unwrap());
+        let left: i64 = conn.query_row("SELECT COUNT(*) FROM assets", [], |row| row.get(0)).unwrap();
+        assert_eq!(left, 1);
+    }
+}
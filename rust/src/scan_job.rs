@@ -0,0 +1,131 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::db;
+use crate::roots;
+use crate::scanner::{self, CancelToken, ScanOptions, ScanSummary};
+
+/// How often the worker forwards progress to the main thread.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub(crate) enum ScanKind {
+    Packs,
+    Individual(Vec<String>),
+    AllRoots(Vec<String>),
+    /// `AllRoots`, filling a database `recover_database` just rebuilt.
+    Recovery(Vec<String>),
+}
+
+impl ScanKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScanKind::Packs => "packs",
+            ScanKind::Individual(_) => "individual",
+            ScanKind::AllRoots(_) => "all_roots",
+            ScanKind::Recovery(_) => "recovery",
+        }
+    }
+}
+
+pub(crate) enum ScanEvent {
+    Progress(ScanSummary),
+    Finished(ScanSummary),
+    Cancelled(ScanSummary),
+    Failed(String),
+}
+
+/// A scan running on its own thread with its own SQLite connection.
+///
+/// The worker owns everything it touches and only talks back through a channel, so the
+/// `AssetManager` that started it can be freed at any time. Once the receiver is gone the
+/// worker's sends fail silently; dropping the job also cancels it and rolls the scan back.
+pub(crate) struct ScanJob {
+    pub kind: ScanKind,
+    pub path: String,
+    cancel: CancelToken,
+    receiver: Receiver<ScanEvent>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ScanJob {
+    pub fn spawn(db_path: String, path: String, kind: ScanKind, options: ScanOptions) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = CancelToken::default();
+        let worker_path = path.clone();
+        let worker_kind = kind.clone();
+        let worker_cancel = cancel.clone();
+
+        let handle = thread::Builder::new()
+            .name("AssetManager scan".to_string())
+            .spawn(move || run(sender, db_path, worker_path, worker_kind, options, worker_cancel))?;
+
+        Ok(Self {
+            kind,
+            path,
+            cancel,
+            receiver,
+            handle: Some(handle),
+        })
+    }
+
+    /// Drain every event the worker has sent since the last call.
+    pub fn poll(&self) -> Vec<ScanEvent> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// Ask the worker to stop at the next WalkDir entry. It still reports back with a `Cancelled` event.
+    pub fn cancel(&self, commit_partial: bool) {
+        self.cancel.cancel(commit_partial);
+    }
+
+    /// Wait for the worker thread to exit. Only call this after a `Finished`/`Cancelled`/`Failed` event.
+    pub fn join(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ScanJob {
+    fn drop(&mut self) {
+        // Nobody is listening anymore. A finished worker ignores this.
+        self.cancel.cancel(false);
+    }
+}
+
+fn run(sender: Sender<ScanEvent>, db_path: String, path: String, kind: ScanKind, options: ScanOptions, cancel: CancelToken) {
+    let mut conn = match db::open(&db_path) {
+        Ok(conn) => conn,
+        Err(e) => {
+            let _ = sender.send(ScanEvent::Failed(e.to_string()));
+            return;
+        }
+    };
+
+    let mut last_report = Instant::now();
+    let mut on_progress = |summary: &ScanSummary| {
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            let _ = sender.send(ScanEvent::Progress(summary.clone()));
+        }
+    };
+
+    let result = match &kind {
+        ScanKind::Packs => scanner::scan_directory(&mut conn, &path, &options, &cancel, &mut on_progress),
+        ScanKind::Individual(extensions) => {
+            scanner::scan_individual_directory(&mut conn, &path, extensions, &options, &cancel, &mut on_progress)
+        }
+        ScanKind::AllRoots(extensions) | ScanKind::Recovery(extensions) => {
+            roots::scan_all_roots(&mut conn, &options, extensions, &cancel, &mut on_progress)
+        }
+    };
+
+    let event = match result {
+        Ok(summary) if summary.cancelled => ScanEvent::Cancelled(summary),
+        Ok(summary) => ScanEvent::Finished(summary),
+        Err(e) => ScanEvent::Failed(e.to_string()),
+    };
+    let _ = sender.send(event);
+}
@@ -0,0 +1,1470 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Instant, UNIX_EPOCH};
+
+use rayon::prelude::*;
+use regex::Regex;
+use rusqlite::{params, Connection, Result as SqlResult};
+use walkdir::WalkDir;
+
+use crate::ignore_rules::IgnoreRules;
+use crate::roots;
+use crate::search_key::{natural_key, search_key, split_tokens};
+use crate::tags;
+use crate::AssetData;
+
+pub(crate) const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpeg", "jpg", "bmp", "tga", "webp", "svg"];
+
+/// Discovery settings copied out of the `AssetManager` so a scan can run without it.
+#[derive(Debug, Clone)]
+pub(crate) struct ScanOptions {
+    pub preview_file_names: Vec<String>,
+    pub use_first_image: bool,
+    pub use_folder_name: bool,
+    /// Global ignore patterns, applied on top of `.assetignore` files.
+    pub ignore_patterns: Vec<String>,
+    /// Folder [ignore_patterns] are relative to. None means the scanned folder, which is right for a
+    /// full scan; a rescan of a folder inside a library (e.g. from the watcher) sets the library root.
+    pub ignore_root: Option<PathBuf>,
+    /// Discover pack folders on a thread pool instead of walking the tree one folder at a time.
+    pub parallel: bool,
+    /// Record how big packs are and when they last changed, for sorting by size or modification time.
+    /// That walks every file of a new pack, and of a known pack whose top level changed since its last stamp.
+    pub disk_usage: bool,
+}
+
+/// A file or folder a scan couldn't handle, and why.
+#[derive(Debug, Clone)]
+pub(crate) struct ScanIssue {
+    pub path: String,
+    pub message: String,
+}
+
+impl ScanIssue {
+    fn new(path: impl AsRef<Path>, message: impl ToString) -> Self {
+        Self {
+            path: path.as_ref().to_string_lossy().to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl ScanOptions {
+    fn ignore_rules(&self, base_path: &str) -> IgnoreRules {
+        let root = self.ignore_root.as_deref().unwrap_or(Path::new(base_path));
+        IgnoreRules::new(root, &self.ignore_patterns)
+    }
+}
+
+/// Running totals of a scan, reported while it runs and once it is done.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanSummary {
+    pub dirs_visited: i64,
+    pub assets_found: i64,
+    pub elapsed_ms: i64,
+    pub updated: i64,
+    pub pruned: i64,
+    pub skipped_existing: i64,
+    pub skipped_deleted: i64,
+    pub nested_removed: i64,
+    pub auto_filled: i64,
+    /// Asset.json files that aren't valid JSON for an asset, with the serde error.
+    pub parse_failures: Vec<ScanIssue>,
+    /// Entries that couldn't be read or written on disk.
+    pub io_errors: Vec<ScanIssue>,
+    /// Rows that couldn't be stored, e.g. an Asset.json pointing at a path another asset already has.
+    pub write_errors: Vec<ScanIssue>,
+    pub cancelled: bool,
+}
+
+impl ScanSummary {
+    /// Add the totals of another scan, e.g. the next root of a multi-root scan.
+    pub fn merge(&mut self, other: &ScanSummary) {
+        self.dirs_visited += other.dirs_visited;
+        self.assets_found += other.assets_found;
+        self.elapsed_ms += other.elapsed_ms;
+        self.updated += other.updated;
+        self.pruned += other.pruned;
+        self.skipped_existing += other.skipped_existing;
+        self.skipped_deleted += other.skipped_deleted;
+        self.nested_removed += other.nested_removed;
+        self.auto_filled += other.auto_filled;
+        self.parse_failures.extend(other.parse_failures.iter().cloned());
+        self.io_errors.extend(other.io_errors.iter().cloned());
+        self.write_errors.extend(other.write_errors.iter().cloned());
+        self.cancelled |= other.cancelled;
+    }
+
+    /// Count the outcome of writing the row at [path].
+    fn count_written(&mut self, reingest_id: Option<i64>, path: &str, result: SqlResult<usize>) {
+        match result {
+            Ok(_) if reingest_id.is_some() => self.updated += 1,
+            Ok(_) => self.assets_found += 1,
+            Err(e) => self.write_errors.push(ScanIssue::new(path, e)),
+        }
+    }
+}
+
+/// The assets row an Asset.json was read into, and its (mtime, size) at the time.
+struct JsonStamp {
+    id: i64,
+    path: String,
+    stamp: Option<(i64, i64)>,
+}
+
+/// Modification time (ns since the epoch) and size of a file, used to spot edited Asset.json files
+/// and to sort individual assets by.
+fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((mtime.as_nanos() as i64, meta.len() as i64))
+}
+
+/// Insert a pack row, or overwrite [reingest_id] in place. [json] is the Asset.json the data came from,
+/// [disk] the pack folder's `folder_stamp`.
+fn write_asset(
+    tx: &Connection,
+    reingest_id: Option<i64>,
+    data: &AssetData,
+    json: Option<(&str, Option<(i64, i64)>)>,
+    disk: Option<(i64, i64)>,
+) -> SqlResult<usize> {
+    let (json_path, stamp) = json.unzip();
+    let (mtime, size) = stamp.flatten().unzip();
+    let (disk_mtime, disk_size) = disk.unzip();
+
+    let (written, id) = match reingest_id {
+        Some(id) => {
+            let written = tx.execute(
+                "UPDATE assets SET name = ?1, path = ?2, image_path = ?3, json_path = ?4, json_mtime = ?5, json_size = ?6,
+                    discovered_by = ?7, discovered_pattern = ?8, license = ?9, name_key = ?10, path_key = ?11,
+                    name_tokens = ?12, path_tokens = ?13, natural_key = ?14, disk_mtime = ?15, disk_size = ?16
+                 WHERE id = ?17",
+                params![
+                    data.name,
+                    data.path,
+                    data.image_path.as_deref(),
+                    json_path,
+                    mtime,
+                    size,
+                    data.discovered_by,
+                    data.discovered_pattern,
+                    data.license,
+                    search_key(&data.name),
+                    search_key(&data.path),
+                    split_tokens(&data.name),
+                    split_tokens(&data.path),
+                    natural_key(&data.name),
+                    disk_mtime,
+                    disk_size,
+                    id
+                ],
+            )?;
+            (written, id)
+        }
+        None => {
+            let written = tx.execute(
+                "INSERT INTO assets (name, path, image_path, json_path, json_mtime, json_size, discovered_by, discovered_pattern, license,
+                    name_key, path_key, name_tokens, path_tokens, natural_key, disk_mtime, disk_size, added_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, unixepoch())",
+                params![
+                    data.name,
+                    data.path,
+                    data.image_path.as_deref(),
+                    json_path,
+                    mtime,
+                    size,
+                    data.discovered_by,
+                    data.discovered_pattern,
+                    data.license,
+                    search_key(&data.name),
+                    search_key(&data.path),
+                    split_tokens(&data.name),
+                    split_tokens(&data.path),
+                    natural_key(&data.name),
+                    disk_mtime,
+                    disk_size
+                ],
+            )?;
+            (written, tx.last_insert_rowid())
+        }
+    };
+
+    if written > 0 {
+        tags::set_tags(tx, "assets", id, &data.tags)?;
+    }
+    Ok(written)
+}
+
+/// Latest modification time (ns since the epoch) of a folder or anything in it, and the total size of
+/// its files. Symlinks are not followed.
+fn folder_stamp(path: &Path) -> Option<(i64, i64)> {
+    let mut latest = 0i64;
+    let mut size = 0i64;
+    for entry in WalkDir::new(path).follow_links(false).into_iter().flatten() {
+        let Ok(meta) = entry.metadata() else { continue };
+        if let Some(mtime) = meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+            latest = latest.max(mtime.as_nanos() as i64);
+        }
+        if meta.is_file() {
+            size += meta.len() as i64;
+        }
+    }
+    (latest > 0).then_some((latest, size))
+}
+
+/// `folder_stamp` of a pack that is already indexed with modification time [stored], or None if it's
+/// not needed: the pack's folder and the entries directly in it are no newer than [stored]. Changes
+/// deeper down are only picked up along with one of those.
+fn refreshed_folder_stamp(path: &Path, stored: Option<i64>, options: &ScanOptions) -> Option<(i64, i64)> {
+    if !options.disk_usage {
+        return None;
+    }
+    let Some(stored) = stored else { return folder_stamp(path) };
+
+    let newer = |meta: std::fs::Metadata| {
+        meta.modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .is_some_and(|mtime| mtime.as_nanos() as i64 > stored)
+    };
+    let changed = std::fs::symlink_metadata(path).is_ok_and(newer)
+        || std::fs::read_dir(path).is_ok_and(|entries| entries.flatten().any(|e| e.metadata().is_ok_and(newer)));
+    if changed {
+        folder_stamp(path)
+    } else {
+        None
+    }
+}
+
+/// Update the modification time and size on disk, used to sort by, of a row that is already indexed.
+fn refresh_disk_stamp(conn: &Connection, table: &str, path: &str, stamp: Option<(i64, i64)>) -> SqlResult<()> {
+    let Some((mtime, size)) = stamp else { return Ok(()) };
+    conn.prepare_cached(&format!(
+        "UPDATE {} SET disk_mtime = ?1, disk_size = ?2 WHERE path = ?3 AND (disk_mtime IS NOT ?1 OR disk_size IS NOT ?2)",
+        table
+    ))?
+    .execute(params![mtime, size, path])?;
+    Ok(())
+}
+
+/// Shared flag a scan checks between WalkDir entries. Clones refer to the same scan.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    commit_partial: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Ask the scan to stop. With [commit_partial] the rows inserted so far are kept,
+    /// otherwise the scan's transaction is rolled back.
+    pub fn cancel(&self, commit_partial: bool) {
+        self.commit_partial.store(commit_partial, Ordering::SeqCst);
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn commit_partial(&self) -> bool {
+        self.commit_partial.load(Ordering::SeqCst)
+    }
+}
+
+/// Commit or roll back a scan's transaction depending on how it ended.
+pub(crate) fn finish_transaction(tx: rusqlite::Transaction, summary: &mut ScanSummary, cancel: &CancelToken) -> SqlResult<()> {
+    if !cancel.is_cancelled() {
+        return tx.commit();
+    }
+
+    summary.cancelled = true;
+    if cancel.commit_partial() {
+        tx.commit()
+    } else {
+        summary.assets_found = 0;
+        summary.updated = 0;
+        summary.pruned = 0;
+        summary.nested_removed = 0;
+        tx.rollback()
+    }
+}
+
+/// Delete the rows of [table] under [base_path] whose path no longer exists on disk, or is now ignored.
+/// Rows elsewhere aren't read, so rescanning one folder (e.g. from the watcher) doesn't cost a pass over the library.
+///
+/// Does nothing if [base_path] itself is missing, so an unmounted drive doesn't empty the library.
+pub(crate) fn prune_missing(conn: &Connection, table: &str, base_path: &str, rules: &mut IgnoreRules) -> SqlResult<i64> {
+    let root = Path::new(base_path);
+    if !root.is_dir() {
+        return Ok(0);
+    }
+
+    let mut missing: Vec<i64> = Vec::new();
+    {
+        let mut stmt = conn.prepare(&format!("SELECT id, path FROM {} WHERE {}", table, roots::under("path", 1)))?;
+        let rows = stmt.query_map(params![roots::slashed(base_path)], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for (id, path) in rows.flatten() {
+            let path = Path::new(&path);
+            // Packs are folders, individual assets are files.
+            if !path.exists() || rules.is_ignored(path, table == "assets") {
+                missing.push(id);
+            }
+        }
+    }
+
+    let mut delete = conn.prepare(&format!("DELETE FROM {} WHERE id = ?1", table))?;
+    for id in &missing {
+        delete.execute(params![id])?;
+    }
+
+    Ok(missing.len() as i64)
+}
+
+/// Which discovery rule picked a folder's preview image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PreviewRule {
+    /// Index into `ScanOptions::preview_file_names`.
+    Pattern(usize),
+    FolderName,
+    FirstImage,
+}
+
+impl PreviewRule {
+    /// Name stored in the `discovered_by` column.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PreviewRule::Pattern(_) => "preview_pattern",
+            PreviewRule::FolderName => "folder_name",
+            PreviewRule::FirstImage => "first_image",
+        }
+    }
+}
+
+/// `discovered_by` of packs defined by their Asset.json.
+pub(crate) const DISCOVERED_BY_ASSET_JSON: &str = "asset_json";
+
+/// Why `read_asset_json` couldn't use a file.
+enum AssetJsonError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+}
+
+/// Read an Asset.json. `Ok(None)` means it's empty or lacks a name or path, and should be auto-filled.
+fn read_asset_json(path: &Path) -> Result<Option<AssetData>, AssetJsonError> {
+    let content = std::fs::read_to_string(path).map_err(AssetJsonError::Io)?;
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    let data = serde_json::from_str::<AssetData>(&content).map_err(AssetJsonError::Parse)?;
+    Ok((!data.name.is_empty() && !data.path.is_empty()).then_some(data))
+}
+
+fn folder_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Apply the preview rules to the files directly inside [dir], in order: preview file names,
+/// then the folder name (if enabled), then the first image (if enabled or [fallback_to_first_image]).
+fn find_preview(
+    dir: &Path,
+    folder_name: &str,
+    options: &ScanOptions,
+    fallback_to_first_image: bool,
+) -> std::io::Result<Option<(PreviewRule, String)>> {
+    let files: Vec<_> = std::fs::read_dir(dir)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    let found = |rule: PreviewRule, file: &std::path::PathBuf| Some((rule, file.to_string_lossy().to_string()));
+
+    // First pass: look for specific preview file names (supports regex)
+    for (index, preview_pattern) in options.preview_file_names.iter().enumerate() {
+        // Patterns are regex only if they start with '^'; use (?i) in the pattern for case-insensitive matching.
+        // Otherwise the stem must match exactly (case-insensitive): "Preview" matches "Preview.png",
+        // "preview.jpg" but NOT "Preview1.png".
+        let matched = if preview_pattern.starts_with('^') {
+            match Regex::new(preview_pattern) {
+                Ok(re) => files.iter().find(|f| {
+                    f.file_name().is_some_and(|n| re.is_match(&n.to_string_lossy())) && is_image(f)
+                }),
+                Err(_) => None,
+            }
+        } else {
+            files.iter().find(|f| {
+                f.file_stem().is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case(preview_pattern)) && is_image(f)
+            })
+        };
+
+        if let Some(file) = matched {
+            return Ok(found(PreviewRule::Pattern(index), file));
+        }
+    }
+
+    // Second pass: look for folder name as filename (if enabled)
+    if options.use_folder_name {
+        for ext in &IMAGE_EXTENSIONS {
+            let target_filename = format!("{}.{}", folder_name, ext);
+            if let Some(file) = files.iter().find(|f| {
+                f.file_name().is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(&target_filename))
+            }) {
+                return Ok(found(PreviewRule::FolderName, file));
+            }
+        }
+    }
+
+    // Third pass: use first image if enabled OR if we have empty Asset.json
+    if options.use_first_image || fallback_to_first_image {
+        if let Some(file) = files.iter().find(|f| is_image(f)) {
+            return Ok(found(PreviewRule::FirstImage, file));
+        }
+    }
+
+    Ok(None)
+}
+
+/// What a scan knows about the database before it starts walking. Only packs under the scanned folder, or
+/// read from an Asset.json under it, are loaded: the walk can't reach any others.
+struct ScanState {
+    deleted: HashSet<String>,
+    /// Paths of the packs, with their `disk_mtime`.
+    existing: HashMap<String, Option<i64>>,
+    json_stamps: HashMap<String, JsonStamp>,
+}
+
+impl ScanState {
+    fn load(conn: &Connection, base_path: &str) -> SqlResult<Self> {
+        let deleted: HashSet<String> = {
+            let mut stmt = conn.prepare("SELECT path FROM deleted")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.flatten().collect()
+        };
+
+        let mut existing: HashMap<String, Option<i64>> = HashMap::new();
+        let mut json_stamps: HashMap<String, JsonStamp> = HashMap::new();
+        {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, path, json_path, json_mtime, json_size, disk_mtime FROM assets WHERE {} OR {}",
+                roots::under("path", 1),
+                roots::under("json_path", 1)
+            ))?;
+            let rows = stmt.query_map(params![roots::slashed(base_path)], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                ))
+            })?;
+            for (id, path, json_path, mtime, size, disk_mtime) in rows.flatten() {
+                // Rows stored before Asset.json stamps existed are keyed by their own folder.
+                let json_path = json_path
+                    .unwrap_or_else(|| Path::new(&path).join("Asset.json").to_string_lossy().to_string());
+                json_stamps.insert(json_path, JsonStamp { id, path: path.clone(), stamp: mtime.zip(size) });
+                existing.insert(path, disk_mtime);
+            }
+        }
+
+        Ok(Self {
+            deleted,
+            existing,
+            json_stamps,
+        })
+    }
+}
+
+/// A pack found in a folder, ready to be written.
+struct FoundPack {
+    /// The folder the pack was found in. Its subfolders are never packs of their own.
+    dir: String,
+    data: AssetData,
+    /// Row to overwrite, for an Asset.json that was edited since the last scan.
+    reingest_id: Option<i64>,
+    /// The folder's Asset.json and its stamp, if it has one.
+    json: Option<(String, Option<(i64, i64)>)>,
+    /// `folder_stamp` of the pack's path, if `ScanOptions::disk_usage` is on.
+    disk: Option<(i64, i64)>,
+}
+
+/// What `discover_dir` made of a folder.
+enum DirOutcome {
+    /// Deleted or unreadable: leave it and everything below it alone.
+    Skip,
+    /// Already indexed and unchanged: only its `folder_stamp` is refreshed, if it's stale (see
+    /// `refreshed_folder_stamp`), and nothing below it is looked at.
+    Known { path: String, disk: Option<(i64, i64)> },
+    Pack(Box<FoundPack>),
+    /// Not a pack; look at its subfolders.
+    Descend,
+}
+
+/// Apply the discovery rules to one folder. Doesn't touch the database, so it can run on any thread;
+/// the only side effect is auto-filling an empty Asset.json.
+///
+/// Packs are stamped here too, so a parallel scan walks their contents on the workers.
+fn discover_dir(path: &Path, state: &ScanState, options: &ScanOptions, summary: &mut ScanSummary) -> DirOutcome {
+    let path_str = path.to_string_lossy().to_string();
+
+    // Check if already deleted
+    if state.deleted.contains(&path_str) {
+        summary.skipped_deleted += 1;
+        return DirOutcome::Skip;
+    }
+
+    // Check for Asset.json
+    let asset_json = path.join("Asset.json");
+    let has_asset_json = asset_json.exists();
+    let asset_json_str = asset_json.to_string_lossy().to_string();
+
+    // An Asset.json we have already read is only parsed again if it changed on disk.
+    // The row is then rewritten in place so it keeps its id.
+    let mut reingest_id: Option<i64> = None;
+    if has_asset_json {
+        if let Some(known) = state.json_stamps.get(&asset_json_str) {
+            if known.stamp.is_some() && known.stamp == file_stamp(&asset_json) {
+                summary.skipped_existing += 1;
+                let stored = state.existing.get(&known.path).copied().flatten();
+                return DirOutcome::Known {
+                    path: known.path.clone(),
+                    disk: refreshed_folder_stamp(Path::new(&known.path), stored, options),
+                };
+            }
+            reingest_id = Some(known.id);
+        }
+    }
+
+    // Check if path already exists in database - skip to speed up rescanning
+    if reingest_id.is_none() {
+        if let Some(&stored) = state.existing.get(&path_str) {
+            summary.skipped_existing += 1;
+            return DirOutcome::Known {
+                disk: refreshed_folder_stamp(path, stored, options),
+                path: path_str,
+            };
+        }
+    }
+
+    if has_asset_json {
+        match read_asset_json(&asset_json) {
+            Ok(Some(mut data)) => {
+                data.discovered_by = Some(DISCOVERED_BY_ASSET_JSON.to_string());
+                let stamp = file_stamp(&asset_json);
+                let disk = options.disk_usage.then(|| folder_stamp(Path::new(&data.path))).flatten();
+                return DirOutcome::Pack(Box::new(FoundPack {
+                    dir: path_str,
+                    data,
+                    reingest_id,
+                    json: Some((asset_json_str, stamp)),
+                    disk,
+                }));
+            }
+            Ok(None) => {}
+            // A file that can't be read is left alone, and so is the row it was read into before: it may be
+            // a hand-written Asset.json saved with a typo, which auto-filling would overwrite.
+            Err(AssetJsonError::Parse(e)) => {
+                summary.parse_failures.push(ScanIssue::new(&asset_json, e));
+                return DirOutcome::Skip;
+            }
+            Err(AssetJsonError::Io(e)) => {
+                summary.io_errors.push(ScanIssue::new(&asset_json, e));
+                return DirOutcome::Skip;
+            }
+        }
+    }
+
+    let folder_name = folder_name(path);
+
+    // An Asset.json without usable data always falls back to the first image.
+    let (rule, final_image) = match find_preview(path, &folder_name, options, has_asset_json) {
+        Ok(preview) => preview.unzip(),
+        Err(e) => {
+            summary.io_errors.push(ScanIssue::new(path, e));
+            (None, None)
+        }
+    };
+
+    // If we have an empty Asset.json file, write the auto-discovered data to it
+    if has_asset_json {
+        let auto_data = AssetData {
+            id: None,
+            name: folder_name.clone(),
+            path: path_str.clone(),
+            image_path: final_image.clone().or(Some(String::new())),  // Empty string if no image found
+            tags: Vec::new(),
+            license: None,
+            root_id: None,
+            discovered_by: None,
+            discovered_pattern: None,
+        };
+
+        // Write the auto-discovered data to Asset.json
+        if let Ok(json_content) = serde_json::to_string_pretty(&auto_data) {
+            match std::fs::write(&asset_json, json_content) {
+                Ok(()) => summary.auto_filled += 1,
+                Err(e) => summary.io_errors.push(ScanIssue::new(&asset_json, e)),
+            }
+        }
+    }
+
+    // Insert asset even without an image if Asset.json exists
+    if final_image.is_none() && !has_asset_json {
+        return DirOutcome::Descend;
+    }
+
+    let data = AssetData {
+        id: None,
+        name: folder_name,
+        path: path_str.clone(),
+        image_path: final_image,
+        tags: Vec::new(),
+        license: None,
+        root_id: None,
+        // An auto-filled Asset.json without a preview is still an Asset.json pack.
+        discovered_by: Some(rule.map_or(DISCOVERED_BY_ASSET_JSON, |r| r.name()).to_string()),
+        discovered_pattern: match rule {
+            Some(PreviewRule::Pattern(index)) => options.preview_file_names.get(index).cloned(),
+            _ => None,
+        },
+    };
+    // Stamp after the auto-fill above so the rewritten file doesn't look edited next time.
+    let json = has_asset_json.then(|| (asset_json_str, file_stamp(&asset_json)));
+    DirOutcome::Pack(Box::new(FoundPack {
+        dir: path_str,
+        data,
+        reingest_id,
+        json,
+        disk: options.disk_usage.then(|| folder_stamp(path)).flatten(),
+    }))
+}
+
+/// Write a pack found by `discover_dir` inside the scan's transaction.
+fn store_pack(tx: &Connection, pack: &FoundPack, summary: &mut ScanSummary) -> SqlResult<()> {
+    if pack.json.is_some() {
+        // Remove any previously-indexed assets living in subdirectories of this pack.
+        // Within the open transaction this also sees rows inserted earlier in this scan.
+        let pattern = format!("{}/%", pack.dir);
+        let removed = tx.execute("DELETE FROM assets WHERE path LIKE ?1", params![pattern])?;
+        summary.nested_removed += removed as i64;
+    }
+
+    let json = pack.json.as_ref().map(|(path, stamp)| (path.as_str(), *stamp));
+    let result = write_asset(tx, pack.reingest_id, &pack.data, json, pack.disk);
+    summary.count_written(pack.reingest_id, &pack.data.path, result);
+    Ok(())
+}
+
+fn is_macosx(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == "__MACOSX")
+}
+
+pub(crate) fn scan_directory(
+    conn: &mut Connection,
+    base_path: &str,
+    options: &ScanOptions,
+    cancel: &CancelToken,
+    on_progress: &mut dyn FnMut(&ScanSummary),
+) -> SqlResult<ScanSummary> {
+    let tx = conn.transaction()?;
+    let mut summary = scan_directory_in(&tx, base_path, options, cancel, on_progress)?;
+    finish_transaction(tx, &mut summary, cancel)?;
+    Ok(summary)
+}
+
+/// `scan_directory` inside the caller's transaction [tx], which the caller commits or rolls back.
+pub(crate) fn scan_directory_in(
+    tx: &Connection,
+    base_path: &str,
+    options: &ScanOptions,
+    cancel: &CancelToken,
+    on_progress: &mut dyn FnMut(&ScanSummary),
+) -> SqlResult<ScanSummary> {
+    let started = Instant::now();
+    let mut summary = ScanSummary::default();
+
+    // Same single-connection / single-transaction strategy as scan_individual_directory:
+    // opening a fresh connection per directory and committing every INSERT on its own made
+    // a fresh scan of a large tree slow. Preload the deleted/existing paths once, then do
+    // all writes inside one transaction.
+
+    let state = ScanState::load(tx, base_path)?;
+    let mut rules = options.ignore_rules(base_path);
+
+    summary.pruned = prune_missing(tx, "assets", base_path, &mut rules)?;
+
+    if options.parallel {
+        walk_parallel(tx, Path::new(base_path), &state, options, rules, cancel, &mut summary, on_progress)?;
+    } else {
+        walk_sequential(tx, Path::new(base_path), &state, options, rules, cancel, &mut summary, on_progress)?;
+    }
+
+    roots::link_to_roots(tx)?;
+
+    summary.elapsed_ms = started.elapsed().as_millis() as i64;
+    Ok(summary)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn walk_sequential(
+    tx: &Connection,
+    base_path: &Path,
+    state: &ScanState,
+    options: &ScanOptions,
+    mut rules: IgnoreRules,
+    cancel: &CancelToken,
+    summary: &mut ScanSummary,
+    on_progress: &mut dyn FnMut(&ScanSummary),
+) -> SqlResult<()> {
+    let mut walker = WalkDir::new(base_path)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|e| !rules.is_ignored(e.path(), e.file_type().is_dir()));
+
+    while let Some(entry) = walker.next() {
+        if cancel.is_cancelled() {
+            break;
+        }
+
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                let path = e.path().map(Path::to_path_buf).unwrap_or_default();
+                summary.io_errors.push(ScanIssue::new(path, e));
+                continue;
+            }
+        };
+
+        let path = entry.path();
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        // Skip macOS resource-fork folders (and everything inside them)
+        if is_macosx(path) {
+            walker.skip_current_dir();
+            continue;
+        }
+
+        summary.dirs_visited += 1;
+        on_progress(summary);
+
+        match discover_dir(path, state, options, summary) {
+            DirOutcome::Skip => walker.skip_current_dir(),
+            DirOutcome::Known { path, disk } => {
+                refresh_disk_stamp(tx, "assets", &path, disk)?;
+                walker.skip_current_dir();
+            }
+            DirOutcome::Pack(pack) => {
+                store_pack(tx, &pack, summary)?;
+                walker.skip_current_dir();
+            }
+            DirOutcome::Descend => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// One folder handled by a `walk_parallel` worker.
+struct DirReport {
+    /// False for a folder whose listing failed; it was already counted when it was visited.
+    visited: bool,
+    /// This folder's share of the totals: skips, auto-fills and issues.
+    summary: ScanSummary,
+    outcome: DirOutcome,
+}
+
+/// Read-only context shared by the `walk_parallel` workers.
+struct ParallelWalk<'a> {
+    state: &'a ScanState,
+    options: &'a ScanOptions,
+    cancel: &'a CancelToken,
+    sender: Sender<DirReport>,
+}
+
+impl ParallelWalk<'_> {
+    /// Discover [dir] and, unless it's a pack, its subfolders in parallel.
+    /// [descend] is false for symlinked folders, which WalkDir reports but doesn't enter either.
+    /// Each folder gets its own copy of [rules], so the workers never wait on each other's lookups.
+    fn visit(&self, dir: &Path, descend: bool, mut rules: IgnoreRules) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+
+        let mut summary = ScanSummary::default();
+        let outcome = discover_dir(dir, self.state, self.options, &mut summary);
+        let descend = descend && matches!(outcome, DirOutcome::Descend);
+
+        // The writer hung up: the scan was cancelled.
+        if self.sender.send(DirReport { visited: true, summary, outcome }).is_err() || !descend {
+            return;
+        }
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.report_issue(ScanIssue::new(dir, e));
+                return;
+            }
+        };
+
+        // Same filters as walk_sequential, in the same order.
+        let mut subdirs: Vec<(PathBuf, bool)> = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(e) => {
+                    self.report_issue(ScanIssue::new(dir, e));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let is_real_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if !path.is_dir() || is_macosx(&path) {
+                continue;
+            }
+            if rules.is_ignored(&path, is_real_dir) {
+                continue;
+            }
+            subdirs.push((path, is_real_dir));
+        }
+
+        subdirs.par_iter().for_each(|(path, is_real_dir)| self.visit(path, *is_real_dir, rules.clone()));
+    }
+
+    fn report_issue(&self, issue: ScanIssue) {
+        let mut summary = ScanSummary::default();
+        summary.io_errors.push(issue);
+        let _ = self.sender.send(DirReport { visited: false, summary, outcome: DirOutcome::Skip });
+    }
+}
+
+/// Parallel version of `walk_sequential`: rayon workers run `discover_dir` on every folder while this
+/// thread, the only one touching the transaction, writes what they find.
+///
+/// Packs, skips and counts are the same as the sequential walk; only the order rows are written in differs.
+#[allow(clippy::too_many_arguments)]
+fn walk_parallel(
+    tx: &Connection,
+    base_path: &Path,
+    state: &ScanState,
+    options: &ScanOptions,
+    mut rules: IgnoreRules,
+    cancel: &CancelToken,
+    summary: &mut ScanSummary,
+    on_progress: &mut dyn FnMut(&ScanSummary),
+) -> SqlResult<()> {
+    // WalkDir reports a missing or unreadable root as an error entry.
+    if let Err(e) = std::fs::metadata(base_path) {
+        summary.io_errors.push(ScanIssue::new(base_path, e));
+        return Ok(());
+    }
+    if !base_path.is_dir() || is_macosx(base_path) || rules.is_ignored(base_path, !base_path.is_symlink()) {
+        return Ok(());
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let walk = ParallelWalk {
+        state,
+        options,
+        cancel,
+        sender,
+    };
+
+    thread::scope(|scope| {
+        scope.spawn(move || walk.visit(base_path, true, rules));
+
+        // Dropping the receiver on cancel or error makes the workers' sends fail, which stops them.
+        for report in receiver {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            if report.visited {
+                summary.dirs_visited += 1;
+            }
+            summary.merge(&report.summary);
+            match report.outcome {
+                DirOutcome::Known { path, disk } => refresh_disk_stamp(tx, "assets", &path, disk)?,
+                DirOutcome::Pack(pack) => store_pack(tx, &pack, summary)?,
+                DirOutcome::Skip | DirOutcome::Descend => {}
+            }
+            if report.visited {
+                on_progress(summary);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// What `scan_directory` would decide for a folder.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Decision {
+    AssetJson,
+    Preview(PreviewRule),
+    NoPreview,
+    Deleted,
+    Ignored,
+}
+
+impl Decision {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Decision::AssetJson => DISCOVERED_BY_ASSET_JSON,
+            Decision::Preview(rule) => rule.name(),
+            Decision::NoPreview => "none",
+            Decision::Deleted => "deleted",
+            Decision::Ignored => "ignored",
+        }
+    }
+}
+
+/// One folder of an `explain_scan` run.
+#[derive(Debug, Clone)]
+pub(crate) struct DirExplanation {
+    pub path: String,
+    pub decision: Decision,
+    /// The Asset.json or preview image the decision is based on.
+    pub matched_file: Option<String>,
+    pub has_asset_json: bool,
+    pub is_asset: bool,
+    pub skipped_subdirs: bool,
+    /// Why the folder's Asset.json or file list couldn't be read, if it couldn't.
+    pub error: Option<String>,
+}
+
+/// Walk [base_path] with the same rules as `scan_directory`, without writing anything.
+///
+/// Folders that are already indexed are explained as if they weren't, so a rescan after changing
+/// the preview settings can be previewed. Only the deleted list is read from the database.
+pub(crate) fn explain_scan(conn: &Connection, base_path: &str, options: &ScanOptions) -> SqlResult<Vec<DirExplanation>> {
+    let deleted: HashSet<String> = {
+        let mut stmt = conn.prepare("SELECT path FROM deleted")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.flatten().collect()
+    };
+
+    let mut rules = options.ignore_rules(base_path);
+    let mut explanations = Vec::new();
+    let mut walker = WalkDir::new(base_path).follow_links(false).into_iter();
+
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                explanations.push(DirExplanation {
+                    path: e.path().map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+                    decision: Decision::NoPreview,
+                    matched_file: None,
+                    has_asset_json: false,
+                    is_asset: false,
+                    skipped_subdirs: false,
+                    error: Some(e.to_string()),
+                });
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let path_str = path.to_string_lossy().to_string();
+        let asset_json = path.join("Asset.json");
+        let mut explanation = DirExplanation {
+            path: path_str.clone(),
+            decision: Decision::NoPreview,
+            matched_file: None,
+            has_asset_json: asset_json.exists(),
+            is_asset: false,
+            skipped_subdirs: true,
+            error: None,
+        };
+
+        if path.components().any(|c| c.as_os_str() == "__MACOSX") || rules.is_ignored(path, true) {
+            explanation.decision = Decision::Ignored;
+        } else if deleted.contains(&path_str) {
+            explanation.decision = Decision::Deleted;
+        } else {
+            let mut unreadable_json = false;
+            if explanation.has_asset_json {
+                match read_asset_json(&asset_json) {
+                    Ok(Some(_)) => {
+                        explanation.decision = Decision::AssetJson;
+                        explanation.matched_file = Some(asset_json.to_string_lossy().to_string());
+                    }
+                    Ok(None) => {}
+                    Err(AssetJsonError::Parse(e)) => explanation.error = Some(e.to_string()),
+                    Err(AssetJsonError::Io(e)) => explanation.error = Some(e.to_string()),
+                }
+                unreadable_json = explanation.error.is_some();
+            }
+
+            if explanation.decision != Decision::AssetJson && !unreadable_json {
+                match find_preview(path, &folder_name(path), options, explanation.has_asset_json) {
+                    Ok(Some((rule, image))) => {
+                        explanation.decision = Decision::Preview(rule);
+                        explanation.matched_file = Some(image);
+                    }
+                    Ok(None) => {}
+                    Err(e) => explanation.error = Some(e.to_string()),
+                }
+            }
+
+            // Same as the scan: an Asset.json makes the folder a pack even without a preview, and one that
+            // can't be read makes the scan skip the folder until it can.
+            explanation.is_asset =
+                !unreadable_json && (explanation.decision != Decision::NoPreview || explanation.has_asset_json);
+            explanation.skipped_subdirs = explanation.is_asset || unreadable_json;
+        }
+
+        if explanation.skipped_subdirs {
+            walker.skip_current_dir();
+        }
+        explanations.push(explanation);
+    }
+
+    Ok(explanations)
+}
+
+pub(crate) fn scan_individual_directory(
+    conn: &mut Connection,
+    base_path: &str,
+    extensions: &[String],
+    options: &ScanOptions,
+    cancel: &CancelToken,
+    on_progress: &mut dyn FnMut(&ScanSummary),
+) -> SqlResult<ScanSummary> {
+    let tx = conn.transaction()?;
+    let mut summary = scan_individual_directory_in(&tx, base_path, extensions, options, cancel, on_progress)?;
+    finish_transaction(tx, &mut summary, cancel)?;
+    Ok(summary)
+}
+
+/// `scan_individual_directory` inside the caller's transaction [tx], which the caller commits or rolls back.
+pub(crate) fn scan_individual_directory_in(
+    tx: &Connection,
+    base_path: &str,
+    extensions: &[String],
+    options: &ScanOptions,
+    cancel: &CancelToken,
+    on_progress: &mut dyn FnMut(&ScanSummary),
+) -> SqlResult<ScanSummary> {
+    let started = Instant::now();
+    let mut summary = ScanSummary::default();
+
+    let exts_lower: Vec<String> = extensions.iter().map(|e| e.to_lowercase()).collect();
+
+    // A large asset tree can hold tens of thousands of matching files. Opening a fresh
+    // connection per file (and committing each INSERT on its own) made this scan freeze
+    // the editor for minutes. Instead: open one connection, load the existing/deleted
+    // paths into memory once, and insert everything inside a single transaction.
+
+    // Paths with their modification time and size on disk.
+    let mut existing: HashMap<String, Option<(i64, i64)>> = HashMap::new();
+    {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT path, disk_mtime, disk_size FROM individual_assets WHERE {}",
+            roots::under("path", 1)
+        ))?;
+        let rows = stmt.query_map(params![roots::slashed(base_path)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, Option<i64>>(2)?))
+        })?;
+        for (path, mtime, size) in rows.flatten() {
+            existing.insert(path, mtime.zip(size));
+        }
+    }
+
+    let mut deleted: HashSet<String> = HashSet::new();
+    {
+        let mut stmt = tx.prepare("SELECT path FROM deleted")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        for row in rows.flatten() {
+            deleted.insert(row);
+        }
+    }
+
+    let mut rules = options.ignore_rules(base_path);
+
+    summary.pruned = prune_missing(tx, "individual_assets", base_path, &mut rules)?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO individual_assets (name, path, name_key, path_key, name_tokens, path_tokens, natural_key, image_path,
+                disk_mtime, disk_size, added_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, unixepoch())",
+        )?;
+
+        for entry in WalkDir::new(base_path)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|e| !rules.is_ignored(e.path(), e.file_type().is_dir()))
+        {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let entry = match entry {
+                Ok(e) => e,
+                Err(e) => {
+                    let path = e.path().map(Path::to_path_buf).unwrap_or_default();
+                    summary.io_errors.push(ScanIssue::new(path, e));
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+
+            // Skip macOS resource-fork folders (and everything inside them)
+            if path.components().any(|c| c.as_os_str() == "__MACOSX") {
+                continue;
+            }
+
+            if entry.file_type().is_dir() {
+                summary.dirs_visited += 1;
+                on_progress(&summary);
+            }
+
+            if !path.is_file() {
+                continue;
+            }
+
+            let ext = match path.extension() {
+                Some(e) => e.to_string_lossy().to_lowercase(),
+                None => continue,
+            };
+
+            if !exts_lower.contains(&ext) {
+                continue;
+            }
+
+            let path_str = path.to_string_lossy().to_string();
+
+            if deleted.contains(&path_str) {
+                summary.skipped_deleted += 1;
+                continue;
+            }
+
+            // Skip duplicates (and remember this path so repeats within the walk are skipped too)
+            let stamp = match existing.entry(path_str.clone()) {
+                Entry::Occupied(mut known) => {
+                    summary.skipped_existing += 1;
+                    if options.disk_usage {
+                        let stamp = file_stamp(path);
+                        if stamp.is_some() && stamp != *known.get() {
+                            refresh_disk_stamp(tx, "individual_assets", &path_str, stamp)?;
+                            known.insert(stamp);
+                        }
+                    }
+                    continue;
+                }
+                Entry::Vacant(new) => *new.insert(file_stamp(path)),
+            };
+
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Unknown".to_string());
+
+            // An image file is its own preview; other file types have no preview image.
+            let image_path = if IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+                path_str.clone()
+            } else {
+                String::new()
+            };
+
+            match insert.execute(params![
+                name,
+                path_str,
+                search_key(&name),
+                search_key(&path_str),
+                split_tokens(&name),
+                split_tokens(&path_str),
+                natural_key(&name),
+                image_path,
+                stamp.map(|(mtime, _)| mtime),
+                stamp.map(|(_, size)| size)
+            ]) {
+                Ok(_) => summary.assets_found += 1,
+                Err(e) => summary.write_errors.push(ScanIssue::new(&path_str, e)),
+            }
+        }
+    }
+    roots::link_to_roots(tx)?;
+
+    summary.elapsed_ms = started.elapsed().as_millis() as i64;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    fn options() -> ScanOptions {
+        ScanOptions {
+            preview_file_names: vec!["Preview".to_string()],
+            use_first_image: false,
+            use_folder_name: true,
+            ignore_patterns: Vec::new(),
+            ignore_root: None,
+            parallel: false,
+            disk_usage: false,
+        }
+    }
+
+    fn scan(conn: &mut Connection, dir: &Path) -> ScanSummary {
+        scan_directory(conn, dir.to_str().unwrap(), &options(), &CancelToken::default(), &mut |_| {}).unwrap()
+    }
+
+    fn names(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT name FROM assets ORDER BY name").unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap()
+    }
+
+    #[test]
+    fn unreadable_asset_json_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+
+        let pack = dir.path().join("Pack");
+        std::fs::create_dir(&pack).unwrap();
+        std::fs::write(pack.join("Preview.png"), b"png").unwrap();
+        let json = pack.join("Asset.json");
+        let valid = format!(r#"{{"name": "Hand Written", "path": "{}", "tags": ["kept"]}}"#, pack.display());
+        std::fs::write(&json, &valid).unwrap();
+        scan(&mut conn, dir.path());
+        assert_eq!(names(&conn), vec!["Hand Written"]);
+
+        // An edit saved with a typo: the file changes on disk, so the pack is read again.
+        let typo = valid.replace("\"tags\"", "tags");
+        std::fs::write(&json, &typo).unwrap();
+        let summary = scan(&mut conn, dir.path());
+        assert_eq!(summary.parse_failures.len(), 1);
+        assert_eq!(summary.auto_filled, 0);
+        assert_eq!(std::fs::read_to_string(&json).unwrap(), typo);
+        assert_eq!(names(&conn), vec!["Hand Written"]);
+
+        // A folder seen for the first time with a broken Asset.json isn't added or filled in either.
+        let new_pack = dir.path().join("New");
+        std::fs::create_dir(&new_pack).unwrap();
+        std::fs::write(new_pack.join("Preview.png"), b"png").unwrap();
+        std::fs::write(new_pack.join("Asset.json"), "{ not json").unwrap();
+        let summary = scan(&mut conn, dir.path());
+        assert_eq!(summary.auto_filled, 0);
+        assert_eq!(std::fs::read_to_string(new_pack.join("Asset.json")).unwrap(), "{ not json");
+        assert_eq!(names(&conn), vec!["Hand Written"]);
+
+        // An empty one is still filled in.
+        std::fs::write(new_pack.join("Asset.json"), "").unwrap();
+        let summary = scan(&mut conn, dir.path());
+        assert_eq!(summary.auto_filled, 1);
+        assert_eq!(names(&conn), vec!["Hand Written", "New"]);
+    }
+
+    #[test]
+    fn partial_rescans_match_global_patterns_at_the_library_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+
+        for pack in ["Textures", "Kits/Textures", "Source"] {
+            std::fs::create_dir_all(dir.path().join(pack)).unwrap();
+            std::fs::write(dir.path().join(pack).join("Preview.png"), b"png").unwrap();
+        }
+        let mut options = options();
+        options.ignore_patterns = vec!["/Textures".to_string(), "Source/".to_string()];
+        options.ignore_root = Some(dir.path().to_path_buf());
+
+        let rescan = |conn: &mut Connection, target: &str| {
+            let target = dir.path().join(target);
+            scan_directory(conn, target.to_str().unwrap(), &options, &CancelToken::default(), &mut |_| {}).unwrap();
+        };
+
+        // `/Textures` only means the one at the root, not the one directly inside the rescanned folder.
+        rescan(&mut conn, "Kits");
+        assert_eq!(names(&conn), vec!["Textures"]);
+
+        // A rescan of an ignored folder itself doesn't index it.
+        rescan(&mut conn, "Textures");
+        rescan(&mut conn, "Source");
+        assert_eq!(names(&conn), vec!["Textures"]);
+    }
+
+    #[test]
+    fn rescans_only_prune_inside_the_rescanned_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+
+        for pack in ["Loose", "Kits/Boxed"] {
+            std::fs::create_dir_all(dir.path().join(pack)).unwrap();
+            std::fs::write(dir.path().join(pack).join("Preview.png"), b"png").unwrap();
+        }
+        scan(&mut conn, dir.path());
+        assert_eq!(names(&conn), vec!["Boxed", "Loose"]);
+
+        std::fs::remove_dir_all(dir.path().join("Loose")).unwrap();
+        std::fs::remove_dir_all(dir.path().join("Kits/Boxed")).unwrap();
+        scan(&mut conn, &dir.path().join("Kits"));
+        assert_eq!(names(&conn), vec!["Loose"]);
+
+        scan(&mut conn, dir.path());
+        assert!(names(&conn).is_empty());
+    }
+
+    /// Every column but the id, ordered by path.
+    fn rows(conn: &Connection) -> Vec<Vec<rusqlite::types::Value>> {
+        let mut stmt = conn.prepare("SELECT * FROM assets ORDER BY path").unwrap();
+        let id = stmt.column_index("id").unwrap();
+        let count = stmt.column_count();
+        stmt.query_map([], |row| (0..count).filter(|&i| i != id).map(|i| row.get(i)).collect())
+            .unwrap()
+            .collect::<SqlResult<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn parallel_scans_store_the_same_rows_as_sequential_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |file: &str, contents: &str| {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("Simple/Preview.png", "png");
+        write("Kits/Inner/Preview.png", "png");
+        write("Kits/Inner/Deeper/Preview.png", "png");
+        write("Kits/Images/b.png", "png");
+        write("Kits/Images/a.jpg", "jpg");
+        write("Kits/Models/Models.png", "png");
+        write("Kits/Models/Raw/rock.glb", "glb");
+        let outer = dir.path().join("Outer");
+        write(
+            "Outer/Asset.json",
+            &format!(r#"{{"name": "Written", "path": "{}", "tags": ["a", "b"]}}"#, outer.display()),
+        );
+        write(".assetignore", "Skipped/\n");
+        write("Skipped/Preview.png", "png");
+        write("Kits/.assetignore", "!Skipped/\nImages/a.jpg\n");
+        write("Kits/Skipped/Preview.png", "png");
+        write("__MACOSX/Pack/Preview.png", "png");
+        for i in 0..20 {
+            write(&format!("Bulk/Pack{}/Preview.png", i), "png");
+        }
+        std::fs::create_dir_all(dir.path().join("Empty")).unwrap();
+
+        let scan_with = |parallel: bool| {
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrations::migrate(&mut conn).unwrap();
+            let mut options = options();
+            options.use_first_image = true;
+            options.ignore_patterns = vec!["Raw/".to_string()];
+            options.parallel = parallel;
+            options.disk_usage = true;
+            let summary =
+                scan_directory(&mut conn, dir.path().to_str().unwrap(), &options, &CancelToken::default(), &mut |_| {})
+                    .unwrap();
+            let counts = (summary.dirs_visited, summary.assets_found, summary.nested_removed, summary.auto_filled);
+            (rows(&conn), counts)
+        };
+
+        let (sequential, counts) = scan_with(false);
+        assert_eq!(sequential.len(), 26);
+        assert_eq!(scan_with(true), (sequential, counts));
+    }
+
+    #[test]
+    fn disk_usage_is_refreshed_when_a_pack_changes_at_the_top() {
+        for parallel in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrations::migrate(&mut conn).unwrap();
+            let mut options = options();
+            options.parallel = parallel;
+            let rescan = |conn: &mut Connection, options: &ScanOptions| {
+                scan_directory(conn, dir.path().to_str().unwrap(), options, &CancelToken::default(), &mut |_| {}).unwrap();
+                conn.query_row("SELECT disk_size FROM assets", [], |row| row.get::<_, Option<i64>>(0)).unwrap()
+            };
+
+            // File times can be coarser than the test is fast, so they're set by hand.
+            let touch = |path: PathBuf, secs: u64| {
+                let time = UNIX_EPOCH + std::time::Duration::from_secs(secs);
+                std::fs::File::open(path).unwrap().set_modified(time).unwrap();
+            };
+            let pack = dir.path().join("Pack");
+            std::fs::create_dir_all(pack.join("Models").join("Deep")).unwrap();
+            std::fs::write(pack.join("Preview.png"), b"png").unwrap();
+            for path in [pack.join("Preview.png"), pack.join("Models/Deep"), pack.join("Models"), pack.clone()] {
+                touch(path, 1000);
+            }
+            assert_eq!(rescan(&mut conn, &options), None);
+
+            // Packs without a stamp get one once it's asked for.
+            options.disk_usage = true;
+            assert_eq!(rescan(&mut conn, &options), Some(3));
+
+            std::fs::write(pack.join("Models/tree.glb"), b"0123456789").unwrap();
+            touch(pack.join("Models/tree.glb"), 1010);
+            touch(pack.join("Models"), 1010);
+            assert_eq!(rescan(&mut conn, &options), Some(13));
+
+            // Only the pack folder and what's directly in it are checked, so a deeper change waits for one of them.
+            std::fs::write(pack.join("Models/Deep/rock.glb"), b"01234").unwrap();
+            touch(pack.join("Models/Deep/rock.glb"), 1020);
+            touch(pack.join("Models/Deep"), 1020);
+            assert_eq!(rescan(&mut conn, &options), Some(13));
+            std::fs::write(pack.join("Readme.txt"), b"!").unwrap();
+            touch(pack.join("Readme.txt"), 1030);
+            touch(pack.clone(), 1030);
+            assert_eq!(rescan(&mut conn, &options), Some(19));
+        }
+    }
+
+    #[test]
+    fn individual_disk_usage_is_only_rewritten_when_it_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        let mut options = options();
+        options.disk_usage = true;
+        let extensions = vec!["glb".to_string()];
+        let rescan = |conn: &mut Connection| {
+            scan_individual_directory(
+                conn,
+                dir.path().to_str().unwrap(),
+                &extensions,
+                &options,
+                &CancelToken::default(),
+                &mut |_| {},
+            )
+            .unwrap();
+            conn.query_row("SELECT disk_size FROM individual_assets", [], |row| row.get::<_, i64>(0)).unwrap()
+        };
+        let changes = |conn: &Connection| conn.query_row("SELECT total_changes()", [], |row| row.get::<_, i64>(0)).unwrap();
+
+        let model = dir.path().join("tree.glb");
+        std::fs::write(&model, b"glb").unwrap();
+        assert_eq!(rescan(&mut conn), 3);
+        let before = changes(&conn);
+        assert_eq!(rescan(&mut conn), 3);
+        assert_eq!(changes(&conn), before);
+
+        std::fs::write(&model, b"bigger glb").unwrap();
+        assert_eq!(rescan(&mut conn), 10);
+    }
+}
@@ -0,0 +1,230 @@
+use rusqlite::{Connection, Result as SqlResult};
+
+use crate::tags;
+
+/// Tables with a full-text index, kept in sync by triggers.
+pub(crate) const INDEXED_TABLES: [&str; 2] = ["assets", "individual_assets"];
+
+/// bm25 weights of the indexed columns: a hit in the name counts most, one in the path least.
+const BM25_WEIGHTS: &str = "10.0, 5.0, 1.0";
+
+/// Create the FTS5 index of [table] and the triggers that keep it in sync, filling it if it's new.
+///
+/// The index keeps its own copy of the search keys of the name, tags and path (see `search_key`), so it
+/// doesn't depend on how [table] stores them. Tags come from the link table, so changing them updates
+/// the index too. The tokenizer splits paths into their folder and file names; the parts of compound
+/// words like "RedTreeLarge" are added from `name_tokens` and `path_tokens` (see `search_key::split_tokens`).
+pub(crate) fn create_fts(conn: &Connection, table: &str) -> SqlResult<()> {
+    create_index(
+        conn,
+        table,
+        "fts",
+        true,
+        "tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'",
+    )?;
+    // Names and tags split into every three-letter run, to find candidates for fuzzy matching.
+    create_index(conn, table, "trigram", false, "tokenize = 'trigram remove_diacritics 1'")
+}
+
+/// FTS5 table `{table}_{suffix}` over name, tags and, if [with_path], path, with its triggers.
+fn create_index(conn: &Connection, table: &str, suffix: &str, with_path: bool, options: &str) -> SqlResult<()> {
+    let index = format!("{}_{}", table, suffix);
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [&index],
+        |row| row.get(0),
+    )?;
+
+    let (columns, new_columns, updated, set_columns) = if with_path {
+        (
+            "name, tags, path",
+            "new.name_key || ' ' || new.name_tokens, '', new.path_key || ' ' || new.path_tokens",
+            "name_key, path_key, name_tokens, path_tokens",
+            "name = new.name_key || ' ' || new.name_tokens, path = new.path_key || ' ' || new.path_tokens",
+        )
+    } else {
+        ("name, tags", "new.name_key, ''", "name_key", "name = new.name_key")
+    };
+
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {i} USING fts5({columns}, {options});
+
+        CREATE TRIGGER IF NOT EXISTS {i}_insert AFTER INSERT ON {t} BEGIN
+            INSERT INTO {i} (rowid, {columns}) VALUES (new.id, {new_columns});
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS {i}_delete AFTER DELETE ON {t} BEGIN
+            DELETE FROM {i} WHERE rowid = old.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS {i}_update AFTER UPDATE OF {updated} ON {t} BEGIN
+            UPDATE {i} SET {set_columns} WHERE rowid = old.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS {link}_{suffix}_insert AFTER INSERT ON {link} BEGIN
+            UPDATE {i} SET tags = {new_tags} WHERE rowid = new.asset_id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS {link}_{suffix}_delete AFTER DELETE ON {link} BEGIN
+            UPDATE {i} SET tags = {old_tags} WHERE rowid = old.asset_id;
+        END;",
+        i = index,
+        t = table,
+        suffix = suffix,
+        columns = columns,
+        options = options,
+        new_columns = new_columns,
+        updated = updated,
+        set_columns = set_columns,
+        link = tags::link_table(table),
+        new_tags = tags::text_column(table, "new.asset_id"),
+        old_tags = tags::text_column(table, "old.asset_id"),
+    ))?;
+
+    if !exists {
+        let tags = tags::text_column(table, &format!("{}.id", table));
+        let values = if with_path {
+            format!("name_key || ' ' || name_tokens, {}, path_key || ' ' || path_tokens", tags)
+        } else {
+            format!("name_key, {}", tags)
+        };
+        conn.execute(
+            &format!("INSERT INTO {i} (rowid, {columns}) SELECT id, {values} FROM {t}", i = index, columns = columns, values = values, t = table),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Drop the full-text indexes of [table] and their triggers, for `create_fts` to rebuild.
+pub(crate) fn drop_indexes(conn: &Connection, table: &str) -> SqlResult<()> {
+    for suffix in ["fts", "trigram"] {
+        conn.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS {t}_{s}_insert;
+            DROP TRIGGER IF EXISTS {t}_{s}_delete;
+            DROP TRIGGER IF EXISTS {t}_{s}_update;
+            DROP TRIGGER IF EXISTS {link}_{s}_insert;
+            DROP TRIGGER IF EXISTS {link}_{s}_delete;
+            DROP TABLE IF EXISTS {t}_{s};",
+            t = table,
+            s = suffix,
+            link = tags::link_table(table)
+        ))?;
+    }
+    Ok(())
+}
+
+/// [table] left-joined with the rows matching the FTS5 query in parameter [param], exposing their bm25
+/// score as `fts.rank` (lower is better, NULL for rows that matched without a full-text term).
+/// Only `fts_id` and `rank` are added, so column names stay unambiguous.
+pub(crate) fn ranked_source(table: &str, param: usize) -> String {
+    format!(
+        "{t} LEFT JOIN (SELECT rowid AS fts_id, bm25({t}_fts, {w}) AS rank FROM {t}_fts WHERE {t}_fts MATCH ?{p}) AS fts
+         ON fts.fts_id = {t}.id",
+        t = table,
+        w = BM25_WEIGHTS,
+        p = param
+    )
+}
+
+/// [table] joined with the rows of its trigram index matching the FTS5 query in parameter [param],
+/// exposing their bm25 score as `fts.rank`.
+pub(crate) fn trigram_source(table: &str, param: usize) -> String {
+    format!(
+        "{t} JOIN (SELECT rowid AS fts_id, rank FROM {t}_trigram WHERE {t}_trigram MATCH ?{p}) AS fts
+         ON fts.fts_id = {t}.id",
+        t = table,
+        p = param
+    )
+}
+
+/// FTS5 query for rows of the trigram index sharing a three-letter run with one of [words].
+/// None if no word is long enough.
+pub(crate) fn trigram_expression(words: &[String]) -> Option<String> {
+    let mut trigrams: Vec<String> = Vec::new();
+    for word in words {
+        let chars: Vec<char> = word.chars().collect();
+        for run in chars.windows(3) {
+            let trigram = format!("\"{}\"", run.iter().collect::<String>().replace('"', "\"\""));
+            if !trigrams.contains(&trigram) {
+                trigrams.push(trigram);
+            }
+        }
+    }
+    if trigrams.is_empty() {
+        None
+    } else {
+        Some(trigrams.join(" OR "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::params;
+
+    use super::*;
+    use crate::migrations;
+    use crate::search_key::{search_key, split_tokens};
+
+    fn insert(conn: &Connection, name: &str, path: &str, tags: &[&str]) -> i64 {
+        conn.execute(
+            "INSERT INTO assets (name, path, name_key, path_key, name_tokens, path_tokens) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![name, path, search_key(name), search_key(path), split_tokens(name), split_tokens(path)],
+        )
+        .unwrap();
+        let id = conn.last_insert_rowid();
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        tags::set_tags(conn, "assets", id, &tags).unwrap();
+        id
+    }
+
+    /// Ids of the rows matching [query], best first.
+    fn ranked(conn: &Connection, query: &str) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT id FROM {} WHERE fts.rank IS NOT NULL ORDER BY fts.rank", ranked_source("assets", 1)))
+            .unwrap();
+        stmt.query_map([query], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap()
+    }
+
+    #[test]
+    fn triggers_keep_the_index_in_sync() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        let id = insert(&conn, "Forest Kit", "/lib/Nature/Kit01", &["Trees"]);
+        assert_eq!(ranked(&conn, "forest"), vec![id]);
+        assert_eq!(ranked(&conn, "nature"), vec![id]);
+        assert_eq!(ranked(&conn, "trees"), vec![id]);
+
+        conn.execute(
+            "UPDATE assets SET name = 'Desert Kit', name_key = ?1, name_tokens = '' WHERE id = ?2",
+            params![search_key("Desert Kit"), id],
+        )
+        .unwrap();
+        assert!(ranked(&conn, "forest").is_empty());
+        assert_eq!(ranked(&conn, "desert"), vec![id]);
+
+        tags::set_tags(&conn, "assets", id, &["Sand".to_string()]).unwrap();
+        assert!(ranked(&conn, "trees").is_empty());
+        assert_eq!(ranked(&conn, "sand"), vec![id]);
+
+        conn.execute("DELETE FROM assets WHERE id = ?1", params![id]).unwrap();
+        assert!(ranked(&conn, "desert").is_empty());
+        assert!(ranked(&conn, "sand").is_empty());
+    }
+
+    #[test]
+    fn name_hits_rank_above_tag_and_path_hits() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        let in_path = insert(&conn, "Pebbles", "/lib/Rock/Pebbles", &[]);
+        let in_tags = insert(&conn, "Cliffs", "/lib/Cliffs", &["Rock"]);
+        let in_name = insert(&conn, "Rock Pack", "/lib/Packs", &[]);
+        insert(&conn, "Water", "/lib/Water", &[]);
+
+        assert_eq!(ranked(&conn, "\"rock\"*"), vec![in_name, in_tags, in_path]);
+        // Parts of compound names are indexed too.
+        let compound = insert(&conn, "RedTreeLarge", "/lib/RedTreeLarge", &[]);
+        assert_eq!(ranked(&conn, "tree"), vec![compound]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Search keys: the form names, paths, tags and queries are compared and sorted in.
+//!
+//! SQLite only folds ASCII case, so keys are computed here and stored next to the text they stand for.
+
+use rusqlite::{params, Connection, Result as SqlResult};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{search_index, tags};
+
+/// [text] without accents, case folded and NFKC normalized, so "Élan" and "elan", "Straße" and
+/// "STRASSE", or full-width and normal letters give the same key.
+///
+/// Kana voicing marks are not accents: "が" and "か" are different letters, so they are kept.
+pub(crate) fn search_key(text: &str) -> String {
+    let stripped: String = text.nfkd().filter(|c| !is_combining_mark(*c) || is_kana_voicing_mark(*c)).collect();
+    caseless::default_case_fold_str(&stripped).nfkc().collect()
+}
+
+/// The combining (han)dakuten, U+3099 and U+309A, that NFKD splits off voiced kana.
+fn is_kana_voicing_mark(c: char) -> bool {
+    matches!(c, '\u{3099}' | '\u{309A}')
+}
+
+/// [search_key] of [text] with every run of digits zero-padded to the same width, so sorting by it
+/// puts "Pack 2" before "Pack 10".
+pub(crate) fn natural_key(text: &str) -> String {
+    fn push_number(key: &mut String, digits: &mut String) {
+        if !digits.is_empty() {
+            key.push_str(&format!("{:0>20}", digits.trim_start_matches('0')));
+            digits.clear();
+        }
+    }
+
+    let mut key = String::new();
+    let mut digits = String::new();
+    for c in search_key(text).chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+        } else {
+            push_number(&mut key, &mut digits);
+            key.push(c);
+        }
+    }
+    push_number(&mut key, &mut digits);
+    key
+}
+
+/// Search keys of the parts of the compound words in [text], space separated, so "RedTreeLarge_02.png"
+/// gives "red tree large". Words are split where lower case turns to upper case, before the last capital
+/// of an acronym ("UIButton" gives "ui button") and between letters and digits ("Tree02" gives
+/// "tree 02"). Words that don't split add nothing: the full-text tokenizer already indexes them, and
+/// already splits at underscores, dashes and other punctuation.
+pub(crate) fn split_tokens(text: &str) -> String {
+    let mut tokens: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric() && !is_combining_mark(c)) {
+        let chars: Vec<char> = word.chars().collect();
+        let mut parts: Vec<String> = Vec::new();
+        let mut part = String::new();
+        // Combining marks belong to the letter before them, so they never start a part.
+        let mut prev: Option<char> = None;
+        for (i, &c) in chars.iter().enumerate() {
+            if is_combining_mark(c) {
+                part.push(c);
+                continue;
+            }
+            if let Some(p) = prev {
+                let next_lower = chars[i + 1..].iter().find(|n| !is_combining_mark(**n)).is_some_and(|n| n.is_lowercase());
+                let boundary = (p.is_lowercase() && c.is_uppercase())
+                    || (p.is_uppercase() && c.is_uppercase() && next_lower)
+                    || (p.is_numeric() != c.is_numeric());
+                if boundary {
+                    parts.push(std::mem::take(&mut part));
+                }
+            }
+            part.push(c);
+            prev = Some(c);
+        }
+        if !parts.is_empty() {
+            parts.push(part);
+            tokens.extend(parts.iter().map(|p| search_key(p)));
+        }
+    }
+    tokens.join(" ")
+}
+
+/// Columns holding the search keys and tokens of the name and path, and the natural sort key of the name.
+const COLUMNS: [&str; 5] = ["name_key", "path_key", "name_tokens", "path_tokens", "natural_key"];
+
+/// Add the missing `name_key`, `path_key`, `name_tokens`, `path_tokens` and `natural_key` columns to
+/// [table] and fill them in.
+///
+/// The full-text indexes are dropped so `create_fts` rebuilds them from the keys. Returns false if the
+/// columns already existed.
+pub(crate) fn migrate(conn: &Connection, table: &str) -> SqlResult<bool> {
+    let mut missing = Vec::new();
+    for column in COLUMNS {
+        let has_column = conn.query_row(
+            &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = '{}'", table, column),
+            [],
+            |row| row.get::<_, i64>(0),
+        )?;
+        if has_column == 0 {
+            missing.push(column);
+        }
+    }
+    if missing.is_empty() {
+        return Ok(false);
+    }
+
+    for column in missing {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} TEXT NOT NULL DEFAULT ''", table, column), [])?;
+    }
+    rekey(conn, table)?;
+
+    search_index::drop_indexes(conn, table)?;
+    Ok(true)
+}
+
+/// Recompute the search keys, tokens and natural sort key of every row of [table].
+pub(crate) fn rekey(conn: &Connection, table: &str) -> SqlResult<()> {
+    let rows: Vec<(i64, String, String)> = {
+        let mut stmt = conn.prepare(&format!("SELECT id, name, path FROM {}", table))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect::<SqlResult<_>>()?
+    };
+    {
+        let mut update = conn.prepare(&format!(
+            "UPDATE {} SET name_key = ?1, path_key = ?2, name_tokens = ?3, path_tokens = ?4, natural_key = ?5 WHERE id = ?6",
+            table
+        ))?;
+        for (id, name, path) in rows {
+            update.execute(params![
+                search_key(&name),
+                search_key(&path),
+                split_tokens(&name),
+                split_tokens(&path),
+                natural_key(&name),
+                id
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+/// Recompute the normalized tag names, aliases and parents after the key function changed.
+/// Tags that now have the same key are merged.
+pub(crate) fn migrate_tags(conn: &Connection) -> SqlResult<()> {
+    let tag_rows: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT id, name FROM tags ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<SqlResult<_>>()?
+    };
+    for (id, name) in tag_rows {
+        let key = tags::normalize(&name);
+        if key == name {
+            continue;
+        }
+        let existing: Option<i64> = conn
+            .query_row("SELECT id FROM tags WHERE name = ?1", params![key], |row| row.get(0))
+            .ok();
+        match existing {
+            Some(keep) => {
+                // Merge into the tag that already has this key, keeping its label.
+                for (_, link) in tags::TAGGED_TABLES {
+                    conn.execute(
+                        &format!("UPDATE OR IGNORE {} SET tag_id = ?1 WHERE tag_id = ?2", link),
+                        params![keep, id],
+                    )?;
+                    conn.execute(&format!("DELETE FROM {} WHERE tag_id = ?1", link), params![id])?;
+                }
+                conn.execute("DELETE FROM tags WHERE id = ?1", params![id])?;
+            }
+            None => {
+                conn.execute("UPDATE tags SET name = ?1 WHERE id = ?2", params![key, id])?;
+            }
+        }
+    }
+
+    for (table, key_column, value_column) in [("tag_aliases", "alias", "tag"), ("tag_parents", "tag", "parent")] {
+        let rows: Vec<(String, String)> = {
+            let mut stmt = conn.prepare(&format!("SELECT {}, {} FROM {}", key_column, value_column, table))?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<SqlResult<_>>()?
+        };
+        conn.execute(&format!("DELETE FROM {}", table), [])?;
+        for (key, value) in rows {
+            let (key, value) = (tags::normalize(&key), tags::normalize(&value));
+            if key != value {
+                conn.execute(
+                    &format!("INSERT OR REPLACE INTO {} ({}, {}) VALUES (?1, ?2)", table, key_column, value_column),
+                    params![key, value],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_key_folds_accents_case_and_width() {
+        assert_eq!(search_key("Élan"), "elan");
+        assert_eq!(search_key("Straße"), search_key("STRASSE"));
+        assert_eq!(search_key("ＡＢＣ１"), "abc1");
+        assert_eq!(search_key("Ａ\u{301}"), "a");
+    }
+
+    #[test]
+    fn search_key_keeps_kana_voicing() {
+        assert_eq!(search_key("が"), "が");
+        assert_eq!(search_key("パ"), "パ");
+        assert_ne!(search_key("がっこう"), search_key("かっこう"));
+        assert_ne!(search_key("パン"), search_key("ハン"));
+        // Decomposed and half-width forms still give the composed key.
+        assert_eq!(search_key("か\u{3099}"), "が");
+        assert_eq!(search_key("ﾊﾟﾝ"), "パン");
+    }
+
+    #[test]
+    fn natural_key_pads_numbers() {
+        assert!(natural_key("Pack 2") < natural_key("Pack 10"));
+        assert_eq!(natural_key("Pack 007"), natural_key("pack 7"));
+        assert_eq!(natural_key("v1.2"), format!("v{:0>20}.{:0>20}", 1, 2));
+        assert_eq!(natural_key("Pack 0"), format!("pack {:0>20}", ""));
+    }
+
+    #[test]
+    fn split_tokens_splits_compound_words() {
+        assert_eq!(split_tokens("RedTreeLarge_02.png"), "red tree large");
+        assert_eq!(split_tokens("UIButton"), "ui button");
+        assert_eq!(split_tokens("Tree02"), "tree 02");
+        assert_eq!(split_tokens("ÉlanUI"), "elan ui");
+        // Words that don't split add nothing.
+        assert_eq!(split_tokens("forest kit"), "");
+        assert_eq!(split_tokens(""), "");
+    }
+}
@@ -0,0 +1,160 @@
+//! Orders assets are listed and searched in.
+
+/// What assets are ordered by. See `AssetManager::set_sort_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortKey {
+    /// Search key of the name: case and accents ignored.
+    Name,
+    /// Like [Name], but numbers by value, so "Pack 2" comes before "Pack 10".
+    Natural,
+    /// When the row was first added to the database.
+    Added,
+    /// Modification time on disk, as of the last scan made while sorting by this or [Size].
+    Modified,
+    /// Size on disk, as of the last scan made while sorting by this or [Modified]. A pack's is the total
+    /// of its folder, counted again when the folder or something directly in it changes.
+    Size,
+    /// When the asset was last imported into a project. Never imported ones come last.
+    Imported,
+    /// Shuffled by a seed, the same for the same seed.
+    Random,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 7] = [
+        SortKey::Name,
+        SortKey::Natural,
+        SortKey::Added,
+        SortKey::Modified,
+        SortKey::Size,
+        SortKey::Imported,
+        SortKey::Random,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Natural => "natural",
+            SortKey::Added => "added",
+            SortKey::Modified => "modified",
+            SortKey::Size => "size",
+            SortKey::Imported => "imported",
+            SortKey::Random => "random",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+}
+
+/// Prime modulus of the random order's hash; squares of numbers below it fit in an i64.
+const RANDOM_MODULUS: i64 = 2_147_483_647;
+
+/// One ORDER BY term.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SortTerm {
+    pub expression: String,
+    pub descending: bool,
+    /// Whether the expression can be NULL. NULLs come last in either direction.
+    pub nullable: bool,
+}
+
+impl SortTerm {
+    fn new(expression: impl Into<String>, descending: bool, nullable: bool) -> Self {
+        Self {
+            expression: expression.into(),
+            descending,
+            nullable,
+        }
+    }
+}
+
+/// ORDER BY clause for [terms], without the keywords.
+pub(crate) fn order_by(terms: &[SortTerm]) -> String {
+    terms
+        .iter()
+        .map(|term| {
+            let direction = if term.descending { "DESC" } else { "ASC" };
+            // Only where needed: NULLS LAST on an ascending term keeps SQLite from using an index for it.
+            let nulls = if term.nullable { " NULLS LAST" } else { "" };
+            format!("{} {}{}", term.expression, direction, nulls)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Terms of relevance order in a search over `search_index::ranked_source`: best bm25 score first,
+/// then rows matched without a full-text term, each by name.
+pub(crate) fn relevance_terms() -> Vec<SortTerm> {
+    with_tiebreak(vec![SortTerm::new("fts.rank", false, true)])
+}
+
+/// [terms] followed by name and id, which make every row's position unique.
+fn with_tiebreak(mut terms: Vec<SortTerm>) -> Vec<SortTerm> {
+    for expression in ["name_key", "name", "id"] {
+        if !terms.iter().any(|term| term.expression == expression) {
+            terms.push(SortTerm::new(expression, false, false));
+        }
+    }
+    terms
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SortOrder {
+    pub key: SortKey,
+    pub descending: bool,
+    /// Seed of [SortKey::Random].
+    pub seed: i64,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Name,
+            descending: false,
+            seed: 0,
+        }
+    }
+}
+
+impl SortOrder {
+    /// Whether this is plain name order, which relevance ranking takes the place of in searches.
+    pub fn is_by_name(self) -> bool {
+        self.key == SortKey::Name && !self.descending
+    }
+
+    /// Identifies the order, so a cursor from another one can be told apart.
+    pub fn signature(self) -> String {
+        let direction = if self.descending { "desc" } else { "asc" };
+        match self.key {
+            SortKey::Random => format!("random:{}:{}", direction, self.seed),
+            key => format!("{}:{}", key.name(), direction),
+        }
+    }
+
+    /// Terms over the columns of `assets` or `individual_assets`. Ties, and rows without the value,
+    /// which always come last, are ordered by name.
+    pub fn terms(self) -> Vec<SortTerm> {
+        let d = self.descending;
+        let terms = match self.key {
+            SortKey::Name => vec![SortTerm::new("name_key", d, false), SortTerm::new("name", d, false)],
+            SortKey::Natural => vec![SortTerm::new("natural_key", d, false), SortTerm::new("name", d, false)],
+            SortKey::Added => vec![SortTerm::new("added_at", d, true), SortTerm::new("id", d, false)],
+            SortKey::Modified => vec![SortTerm::new("disk_mtime", d, true)],
+            SortKey::Size => vec![SortTerm::new("disk_size", d, true)],
+            SortKey::Imported => vec![SortTerm::new("imported_at", d, true)],
+            SortKey::Random => {
+                // Squaring modulo a prime scrambles consecutive ids; the seed shifts which ones.
+                let shifted = format!("((id + {}) * 48271 % {m})", self.seed.rem_euclid(RANDOM_MODULUS), m = RANDOM_MODULUS);
+                vec![SortTerm::new(format!("({s} * {s} % {m})", s = shifted, m = RANDOM_MODULUS), d, false)]
+            }
+        };
+        with_tiebreak(terms)
+    }
+
+    /// ORDER BY clause for this order, without the keywords.
+    pub fn to_sql(self) -> String {
+        order_by(&self.terms())
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
+
+use crate::search_index;
+use crate::search_key::search_key;
+
+/// Asset tables and the tables linking their rows to the shared `tags` catalog.
+pub(crate) const TAGGED_TABLES: [(&str, &str); 2] = [("assets", "asset_tags"), ("individual_assets", "individual_asset_tags")];
+
+/// The table linking rows of [table] to tags.
+pub(crate) fn link_table(table: &str) -> &'static str {
+    TAGGED_TABLES
+        .iter()
+        .find(|(t, _)| *t == table)
+        .map(|(_, link)| *link)
+        .expect("not a tagged table")
+}
+
+/// The form a tag is matched by: its trimmed search key, so "UI", "ui " and "ui" are one tag.
+pub(crate) fn normalize(tag: &str) -> String {
+    search_key(tag.trim())
+}
+
+/// SQL expression with the normalized tags of row [id] of [table] separated by spaces, for the full-text index.
+pub(crate) fn text_column(table: &str, id: &str) -> String {
+    format!(
+        "(SELECT coalesce(group_concat(g.name, ' '), '') FROM {link} l JOIN tags g ON g.id = l.tag_id WHERE l.asset_id = {id})",
+        link = link_table(table),
+        id = id
+    )
+}
+
+/// SQL condition matching rows of [table] tagged [tag] or one of its aliases and children (see `family`).
+/// If [prefix], every tag or alias starting with [tag] counts, with its aliases and children too.
+/// The value is appended to [params] and referenced by number.
+pub(crate) fn condition(table: &str, tag: &str, prefix: bool, params: &mut Vec<String>) -> String {
+    let tag = normalize(tag);
+    let names = if prefix {
+        // GLOB rather than LIKE: it's case-sensitive, so the indexes on the normalized names are used.
+        params.push(format!("{}*", escape_glob(&tag)));
+        prefix_family(params.len())
+    } else {
+        params.push(tag);
+        family(params.len())
+    };
+    format!(
+        "{t}.id IN (SELECT l.asset_id FROM {link} l WHERE l.tag_id IN (SELECT g.id FROM tags g JOIN ({names}) AS f ON g.name = f.name))",
+        t = table,
+        link = link_table(table),
+        names = names
+    )
+}
+
+/// SQL query for the names tag ?[param] stands for: the tag it's an alias of (or itself), that tag's aliases,
+/// and everything below it, through `tag_parents` and `parent/child` names, with their aliases too.
+fn family(param: usize) -> String {
+    family_of(&format!("SELECT coalesce((SELECT tag FROM tag_aliases WHERE alias = ?{p}), ?{p})", p = param))
+}
+
+/// Like `family`, for every tag matching the GLOB pattern ?[param] and every tag an alias matching it stands for.
+fn prefix_family(param: usize) -> String {
+    family_of(&format!(
+        "SELECT name FROM tags WHERE name GLOB ?{p} UNION SELECT tag FROM tag_aliases WHERE alias GLOB ?{p}",
+        p = param
+    ))
+}
+
+/// SQL query for the names in [seed] (a query returning one column) with their aliases and everything below them.
+fn family_of(seed: &str) -> String {
+    format!(
+        "WITH RECURSIVE family(name) AS (
+            SELECT * FROM ({seed})
+            UNION SELECT a.alias FROM tag_aliases a JOIN family f ON a.tag = f.name
+            UNION SELECT t.tag FROM tag_parents t JOIN family f ON t.parent = f.name
+            UNION SELECT g.name FROM tags g JOIN family f ON substr(g.name, 1, length(f.name) + 1) = f.name || '/'
+        )
+        SELECT name FROM family",
+        seed = seed
+    )
+}
+
+fn escape_glob(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '*' | '?' | '[' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Create the tag catalog and link tables. Deleting a row of a tagged table removes its links.
+pub(crate) fn create_tables(conn: &Connection) -> SqlResult<()> {
+    // name is the normalized form tags are matched by, label the spelling it was first added with.
+    // Aliases and parents refer to normalized names, so they can be set up before any asset has the tag.
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            label TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS tag_aliases (
+            alias TEXT PRIMARY KEY,
+            tag TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_tag_aliases_tag ON tag_aliases(tag);
+
+        CREATE TABLE IF NOT EXISTS tag_parents (
+            tag TEXT PRIMARY KEY,
+            parent TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_tag_parents_parent ON tag_parents(parent);",
+    )?;
+
+    for (table, link) in TAGGED_TABLES {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {link} (
+                asset_id INTEGER NOT NULL,
+                tag_id INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (asset_id, tag_id)
+            ) WITHOUT ROWID;
+
+            CREATE INDEX IF NOT EXISTS idx_{link}_tag ON {link}(tag_id, asset_id);
+
+            CREATE TRIGGER IF NOT EXISTS {t}_tags_delete AFTER DELETE ON {t} BEGIN
+                DELETE FROM {link} WHERE asset_id = old.id;
+            END;",
+            t = table,
+            link = link
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Replace the tags of row [id] of [table] with [tags], keeping their order. Blank tags and repeats are dropped.
+pub(crate) fn set_tags(conn: &Connection, table: &str, id: i64, tags: &[String]) -> SqlResult<()> {
+    let link = link_table(table);
+    conn.execute(&format!("DELETE FROM {} WHERE asset_id = ?1", link), params![id])?;
+
+    let mut seen = HashSet::new();
+    let mut insert_tag = conn.prepare_cached("INSERT OR IGNORE INTO tags (name, label) VALUES (?1, ?2)")?;
+    let mut tag_id = conn.prepare_cached("SELECT id FROM tags WHERE name = ?1")?;
+    let mut insert_link =
+        conn.prepare_cached(&format!("INSERT INTO {} (asset_id, tag_id, position) VALUES (?1, ?2, ?3)", link))?;
+
+    for tag in tags {
+        let name = normalize(tag);
+        if name.is_empty() || !seen.insert(name.clone()) {
+            continue;
+        }
+        insert_tag.execute(params![name, tag.trim()])?;
+        let tag_id: i64 = tag_id.query_row(params![name], |row| row.get(0))?;
+        insert_link.execute(params![id, tag_id, seen.len() as i64])?;
+    }
+
+    Ok(())
+}
+
+/// Move tags from the old JSON `tags` column of [table] into the link table, then drop the column.
+///
+/// The full-text index and its triggers read that column, so they are dropped too; `create_fts` rebuilds them.
+pub(crate) fn migrate_json_column(conn: &Connection, table: &str) -> SqlResult<()> {
+    let has_column = conn.query_row(
+        &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = 'tags'", table),
+        [],
+        |row| row.get::<_, i64>(0),
+    )?;
+    if has_column == 0 {
+        return Ok(());
+    }
+
+    let rows: Vec<(i64, Option<String>)> = {
+        let mut stmt = conn.prepare(&format!("SELECT id, tags FROM {}", table))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<SqlResult<_>>()?
+    };
+
+    for (id, json) in rows {
+        let tags: Vec<String> = json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+        set_tags(conn, table, id, &tags)?;
+    }
+
+    search_index::drop_indexes(conn, table)?;
+    conn.execute(&format!("ALTER TABLE {} DROP COLUMN tags", table), [])?;
+    Ok(())
+}
+
+/// Tags used by rows of [table] matching [scope], with how many rows use each, most used first.
+pub(crate) fn tag_counts(conn: &Connection, table: &str, scope: &str) -> SqlResult<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT g.label, COUNT(*) AS uses FROM {link} l
+         JOIN tags g ON g.id = l.tag_id
+         JOIN {t} ON {t}.id = l.asset_id
+         WHERE {scope}
+         GROUP BY g.id
+         ORDER BY uses DESC, g.name",
+        t = table,
+        link = link_table(table),
+        scope = scope
+    ))?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Up to [limit] tags starting with [prefix] and used by a row of either table matching [scope],
+/// most used first. The prefix lookup uses the index on the normalized name.
+pub(crate) fn suggest(conn: &Connection, prefix: &str, limit: i64, scope: &str) -> SqlResult<Vec<String>> {
+    let uses: Vec<String> = TAGGED_TABLES
+        .iter()
+        .map(|(t, link)| {
+            format!(
+                "(SELECT COUNT(*) FROM {link} l JOIN {t} ON {t}.id = l.asset_id WHERE l.tag_id = g.id AND {scope})",
+                t = t,
+                link = link,
+                scope = scope
+            )
+        })
+        .collect();
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT label FROM (SELECT g.label, g.name, {uses} AS uses FROM tags g WHERE g.name GLOB ?1)
+         WHERE uses > 0
+         ORDER BY uses DESC, name
+         LIMIT ?2",
+        uses = uses.join(" + ")
+    ))?;
+    let rows = stmt.query_map(params![format!("{}*", escape_glob(&normalize(prefix))), limit], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// The tag [name] is an alias of, or [name] itself. [name] must be normalized.
+pub(crate) fn canonical(conn: &Connection, name: &str) -> SqlResult<String> {
+    Ok(conn
+        .query_row("SELECT tag FROM tag_aliases WHERE alias = ?1", params![name], |row| row.get(0))
+        .optional()?
+        .unwrap_or_else(|| name.to_string()))
+}
+
+/// Make [alias] an alias of [tag], so searching either matches both. If [tag] is itself an alias,
+/// the alias points at what it stands for. Aliases of [alias] move over to [tag].
+/// Returns false if either is blank or [alias] is the tag [tag] stands for.
+pub(crate) fn add_alias(conn: &mut Connection, alias: &str, tag: &str) -> SqlResult<bool> {
+    let tx = conn.transaction()?;
+    let added = set_alias(&tx, alias, tag)?;
+    tx.commit()?;
+    Ok(added)
+}
+
+/// `add_alias` inside the caller's transaction.
+pub(crate) fn set_alias(conn: &Connection, alias: &str, tag: &str) -> SqlResult<bool> {
+    let alias = normalize(alias);
+    let tag = canonical(conn, &normalize(tag))?;
+    if alias.is_empty() || tag.is_empty() || alias == tag {
+        return Ok(false);
+    }
+
+    conn.execute("UPDATE tag_aliases SET tag = ?1 WHERE tag = ?2", params![tag, alias])?;
+    conn.execute(
+        "INSERT INTO tag_aliases (alias, tag) VALUES (?1, ?2) ON CONFLICT (alias) DO UPDATE SET tag = excluded.tag",
+        params![alias, tag],
+    )?;
+    Ok(true)
+}
+
+/// Returns false if [alias] wasn't an alias.
+pub(crate) fn remove_alias(conn: &Connection, alias: &str) -> SqlResult<bool> {
+    Ok(conn.execute("DELETE FROM tag_aliases WHERE alias = ?1", params![normalize(alias)])? > 0)
+}
+
+/// Every alias with the tag it stands for, by alias.
+pub(crate) fn aliases(conn: &Connection) -> SqlResult<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT alias, tag FROM tag_aliases ORDER BY alias")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Put [tag] below [parent], so searching [parent] matches it too. An empty [parent] removes the parent.
+/// Aliases are resolved first. Returns false if [tag] is blank or [parent] is [tag] or below it.
+pub(crate) fn set_parent(conn: &Connection, tag: &str, parent: &str) -> SqlResult<bool> {
+    let tag = canonical(conn, &normalize(tag))?;
+    if tag.is_empty() {
+        return Ok(false);
+    }
+
+    let parent = normalize(parent);
+    if parent.is_empty() {
+        conn.execute("DELETE FROM tag_parents WHERE tag = ?1", params![tag])?;
+        return Ok(true);
+    }
+
+    let parent = canonical(conn, &parent)?;
+    let cycle: bool = conn.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM ({}) WHERE name = ?2", family(1)),
+        params![tag, parent],
+        |row| row.get(0),
+    )?;
+    if cycle {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "INSERT INTO tag_parents (tag, parent) VALUES (?1, ?2) ON CONFLICT (tag) DO UPDATE SET parent = excluded.parent",
+        params![tag, parent],
+    )?;
+    Ok(true)
+}
+
+/// Every tag with a parent set by `set_parent`, by tag. Tags below a parent by name aren't listed.
+pub(crate) fn parents(conn: &Connection) -> SqlResult<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT tag, parent FROM tag_parents ORDER BY tag")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    fn open() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        conn
+    }
+
+    fn tagged(conn: &Connection, path: &str, tags: &[&str]) -> i64 {
+        conn.execute("INSERT INTO assets (name, path) VALUES (?1, ?1)", params![path]).unwrap();
+        let id = conn.last_insert_rowid();
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        set_tags(conn, "assets", id, &tags).unwrap();
+        id
+    }
+
+    /// Ids of the assets `tag:[tag]` matches, or `tag:[tag]*` if [prefix].
+    fn matching(conn: &Connection, tag: &str, prefix: bool) -> Vec<i64> {
+        let mut params = Vec::new();
+        let sql = format!("SELECT id FROM assets WHERE {} ORDER BY id", condition("assets", tag, prefix, &mut params));
+        let mut stmt = conn.prepare(&sql).unwrap();
+        stmt.query_map(rusqlite::params_from_iter(&params), |row| row.get(0))
+            .unwrap()
+            .collect::<SqlResult<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn tags_match_exactly_or_by_prefix() {
+        let conn = open();
+        let ui = tagged(&conn, "Buttons", &["UI", "2D"]);
+        let build = tagged(&conn, "Scaffold", &["build", "guide"]);
+        let ui_kit = tagged(&conn, "Panels", &["ui kit"]);
+
+        assert_eq!(matching(&conn, "ui", false), vec![ui]);
+        assert_eq!(matching(&conn, " Ui ", false), vec![ui]);
+        assert_eq!(matching(&conn, "ui", true), vec![ui, ui_kit]);
+        assert_eq!(matching(&conn, "gui", true), vec![build]);
+        // Prefixes of the tag, not anywhere inside it.
+        assert!(matching(&conn, "ild", true).is_empty());
+        assert_eq!(matching(&conn, "guide", false), vec![build]);
+        // Glob characters in a tag are matched literally.
+        assert!(matching(&conn, "u?", true).is_empty());
+    }
+
+    #[test]
+    fn set_tags_keeps_order_and_drops_repeats() {
+        let conn = open();
+        let id = tagged(&conn, "Forest", &["Nature", " ", "3D", "nature ", "Trees"]);
+        tagged(&conn, "Jungle", &["NATURE"]);
+
+        let mut stmt = conn
+            .prepare("SELECT g.label FROM asset_tags l JOIN tags g ON g.id = l.tag_id WHERE l.asset_id = ?1 ORDER BY l.position")
+            .unwrap();
+        let labels: Vec<String> = stmt.query_map([id], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap();
+        // The catalog keeps the spelling a tag was first added with.
+        assert_eq!(labels, vec!["Nature", "3D", "Trees"]);
+        let catalog: i64 = conn.query_row("SELECT COUNT(*) FROM tags", [], |row| row.get(0)).unwrap();
+        assert_eq!(catalog, 3);
+    }
+
+    #[test]
+    fn aliases_and_children_expand_tag_searches() {
+        let conn = open();
+        let audio = tagged(&conn, "Music", &["Audio"]);
+        let sfx = tagged(&conn, "Door", &["SFX"]);
+        let steps = tagged(&conn, "Steps", &["audio/footsteps"]);
+        let rain = tagged(&conn, "Rain", &["ambience"]);
+        let other = tagged(&conn, "Sprites", &["2D"]);
+
+        assert!(set_alias(&conn, "sfx", "Audio").unwrap());
+        assert!(set_alias(&conn, "sound", "sfx").unwrap());
+        assert!(set_parent(&conn, "Ambience", "sound").unwrap());
+        assert_eq!(aliases(&conn).unwrap(), vec![("sfx".into(), "audio".into()), ("sound".into(), "audio".into())]);
+        assert_eq!(parents(&conn).unwrap(), vec![("ambience".into(), "audio".into())]);
+
+        let family = vec![audio, sfx, steps, rain];
+        assert_eq!(matching(&conn, "audio", false), family);
+        assert_eq!(matching(&conn, "SOUND", false), family);
+        assert_eq!(matching(&conn, "audio/footsteps", false), vec![steps]);
+        assert_eq!(matching(&conn, "ambience", false), vec![rain]);
+
+        // Prefixes go through aliases and children too.
+        assert_eq!(matching(&conn, "so", true), family);
+        assert_eq!(matching(&conn, "aud", true), family);
+        assert_eq!(matching(&conn, "amb", true), vec![rain]);
+        assert_eq!(matching(&conn, "2", true), vec![other]);
+
+        // A tag can't go below itself or one of its children.
+        assert!(!set_parent(&conn, "audio", "ambience").unwrap());
+        assert!(!set_parent(&conn, "sound", "audio/footsteps").unwrap());
+        assert!(!set_alias(&conn, "audio", "sfx").unwrap());
+
+        assert!(remove_alias(&conn, "sfx").unwrap());
+        assert!(!remove_alias(&conn, "sfx").unwrap());
+        assert_eq!(matching(&conn, "audio", false), vec![audio, steps, rain]);
+        assert_eq!(matching(&conn, "sfx", false), vec![sfx]);
+        assert!(set_parent(&conn, "ambience", "").unwrap());
+        assert_eq!(matching(&conn, "audio", false), vec![audio, steps]);
+    }
+
+    #[test]
+    fn catalog_counts_and_suggests_used_tags() {
+        let conn = open();
+        tagged(&conn, "Forest", &["Nature", "Trees"]);
+        let jungle = tagged(&conn, "Jungle", &["nature", "Tropical"]);
+        tagged(&conn, "Rocks", &["Nature"]);
+        conn.execute("INSERT INTO individual_assets (name, path) VALUES ('tree.glb', '/lib/tree.glb')", []).unwrap();
+        let tree = conn.last_insert_rowid();
+        set_tags(&conn, "individual_assets", tree, &["Trees".to_string(), "Tropical".to_string()]).unwrap();
+        set_tags(&conn, "individual_assets", tree, &["Trees".to_string()]).unwrap();
+
+        assert_eq!(
+            tag_counts(&conn, "assets", "1").unwrap(),
+            vec![("Nature".to_string(), 3), ("Trees".to_string(), 1), ("Tropical".to_string(), 1)]
+        );
+        assert_eq!(tag_counts(&conn, "individual_assets", "1").unwrap(), vec![("Trees".to_string(), 1)]);
+        assert_eq!(
+            tag_counts(&conn, "assets", &format!("assets.id = {}", jungle)).unwrap(),
+            vec![("Nature".to_string(), 1), ("Tropical".to_string(), 1)]
+        );
+
+        // Uses of both tables count; the most used comes first.
+        assert_eq!(suggest(&conn, "T", 10, "1").unwrap(), vec!["Trees", "Tropical"]);
+        assert_eq!(suggest(&conn, "t", 1, "1").unwrap(), vec!["Trees"]);
+        assert_eq!(suggest(&conn, "", 10, "1").unwrap(), vec!["Nature", "Trees", "Tropical"]);
+        // Tags no asset in scope uses any more aren't suggested.
+        conn.execute("DELETE FROM assets WHERE id = ?1", params![jungle]).unwrap();
+        assert_eq!(suggest(&conn, "tr", 10, "1").unwrap(), vec!["Trees"]);
+    }
+}
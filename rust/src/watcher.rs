@@ -0,0 +1,342 @@
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::{params, Connection, Result as SqlResult};
+
+use crate::{db, roots};
+use crate::scanner::{self, CancelToken, ScanOptions};
+
+/// Changes are applied once the library has been quiet for this long...
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+/// ...or after this long, so a long copy still shows up while it runs.
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(3);
+
+pub(crate) enum WatchEvent {
+    Changed,
+    Failed(String),
+}
+
+/// Watches a library root and applies the scanners' discovery rules to whatever changes under it.
+///
+/// Like `ScanJob`, the worker has its own SQLite connection and only reports back through a channel.
+/// Dropping the watcher stops the OS watch, which closes the event channel and ends the worker.
+pub(crate) struct LibraryWatcher {
+    pub root: String,
+    cancel: CancelToken,
+    receiver: Receiver<WatchEvent>,
+    _watcher: RecommendedWatcher,
+}
+
+impl LibraryWatcher {
+    pub fn spawn(db_path: String, root: String, mut options: ScanOptions, extensions: Vec<String>) -> notify::Result<Self> {
+        let (fs_sender, fs_receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(fs_sender)?;
+        watcher.watch(Path::new(&root), RecursiveMode::Recursive)?;
+
+        // Rescans start at whatever changed, but global ignore patterns stay relative to the watched folder.
+        options.ignore_root = Some(PathBuf::from(&root));
+
+        let (sender, receiver) = mpsc::channel();
+        let cancel = CancelToken::default();
+        let worker = Worker {
+            db_path,
+            root: PathBuf::from(&root),
+            options,
+            extensions,
+            cancel: cancel.clone(),
+        };
+
+        thread::Builder::new()
+            .name("AssetManager watcher".to_string())
+            .spawn(move || worker.run(fs_receiver, sender))?;
+
+        Ok(Self {
+            root,
+            cancel,
+            receiver,
+            _watcher: watcher,
+        })
+    }
+
+    /// Drain every event the worker has sent since the last call.
+    pub fn poll(&self) -> Vec<WatchEvent> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+impl Drop for LibraryWatcher {
+    fn drop(&mut self) {
+        // Abandon a rescan that's still running; the next one picks the changes up again.
+        self.cancel.cancel(false);
+    }
+}
+
+struct Worker {
+    db_path: String,
+    root: PathBuf,
+    options: ScanOptions,
+    extensions: Vec<String>,
+    cancel: CancelToken,
+}
+
+/// Paths touched by a batch of filesystem events.
+#[derive(Default)]
+struct Changes {
+    removed: BTreeSet<PathBuf>,
+    created: BTreeSet<PathBuf>,
+    /// The OS dropped events (e.g. the inotify queue overflowed), so the whole root has to be looked at again.
+    rescan: bool,
+}
+
+impl Changes {
+    fn add(&mut self, event: notify::Event) {
+        if event.need_rescan() {
+            self.rescan = true;
+        }
+        let mut paths = event.paths.into_iter();
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_)) | EventKind::Modify(ModifyKind::Any) => {
+                self.created.extend(paths);
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                self.removed.extend(paths);
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                self.created.extend(paths);
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                self.removed.extend(paths.next());
+                self.created.extend(paths);
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in paths {
+                    if path.exists() {
+                        self.created.insert(path);
+                    } else {
+                        self.removed.insert(path);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.rescan && self.removed.is_empty() && self.created.is_empty()
+    }
+}
+
+impl Worker {
+    fn run(self, fs_receiver: Receiver<notify::Result<notify::Event>>, sender: Sender<WatchEvent>) {
+        let mut conn = match db::open(&self.db_path) {
+            Ok(conn) => conn,
+            Err(e) => {
+                let _ = sender.send(WatchEvent::Failed(e.to_string()));
+                return;
+            }
+        };
+
+        // Block until something happens, then keep collecting until things settle down.
+        while let Ok(first) = fs_receiver.recv() {
+            let mut changes = Changes::default();
+            let batch_started = Instant::now();
+            let mut next = Some(first);
+
+            while let Some(event) = next.take() {
+                match event {
+                    Ok(event) => changes.add(event),
+                    Err(e) => {
+                        let _ = sender.send(WatchEvent::Failed(e.to_string()));
+                    }
+                }
+
+                if batch_started.elapsed() >= MAX_BATCH_DELAY {
+                    break;
+                }
+                match fs_receiver.recv_timeout(QUIET_PERIOD) {
+                    Ok(event) => next = Some(event),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            if changes.is_empty() {
+                continue;
+            }
+
+            match self.apply(&mut conn, changes) {
+                Ok(true) => {
+                    if sender.send(WatchEvent::Changed).is_err() {
+                        return;
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    let _ = sender.send(WatchEvent::Failed(e.to_string()));
+                }
+            }
+        }
+    }
+
+    /// Bring the database in line with [changes]. Returns whether any row changed.
+    fn apply(&self, conn: &mut Connection, mut changes: Changes) -> SqlResult<bool> {
+        let mut changed = false;
+
+        let tx = conn.transaction()?;
+        for path in &changes.removed {
+            if path.components().any(|c| c.as_os_str() == "__MACOSX") {
+                continue;
+            }
+            let path_str = path.to_string_lossy().to_string();
+            changed |= remove_under(&tx, "assets", &path_str)? > 0;
+            changed |= remove_under(&tx, "individual_assets", &path_str)? > 0;
+
+            // A pack whose discovered preview was removed no longer satisfies the rule that found it.
+            // Drop it and look at its folder again so another preview rule can pick it up.
+            // Packs added by hand keep their row.
+            let orphaned = tx.execute(
+                "DELETE FROM assets WHERE image_path = ?1 AND json_path IS NULL AND discovered_by IS NOT 'manual'",
+                params![path_str],
+            )?;
+            if orphaned > 0 {
+                changed = true;
+                if let Some(parent) = path.parent() {
+                    changes.created.insert(parent.to_path_buf());
+                }
+            }
+        }
+        tx.commit()?;
+
+        if changes.rescan {
+            changes.created.insert(self.root.clone());
+        }
+        let targets = top_level(&changes.created);
+        if targets.is_empty() {
+            return Ok(changed);
+        }
+
+        let pack_paths: HashSet<String> = {
+            let mut stmt = conn.prepare("SELECT path FROM assets")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.flatten().collect()
+        };
+
+        for target in &targets {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            if !target.starts_with(&self.root) || target.components().any(|c| c.as_os_str() == "__MACOSX") {
+                continue;
+            }
+
+            // Packs are folders: a new file can only turn its own folder into a pack.
+            let dir = if target.is_dir() {
+                target.clone()
+            } else {
+                match target.parent() {
+                    Some(parent) => parent.to_path_buf(),
+                    None => continue,
+                }
+            };
+
+            // Same rule as the scanner: nothing inside an existing pack becomes a pack of its own.
+            let inside_pack = dir
+                .ancestors()
+                .skip(1)
+                .take_while(|a| a.starts_with(&self.root))
+                .any(|a| pack_paths.contains(a.to_string_lossy().as_ref()));
+
+            if !inside_pack && dir.exists() {
+                let dir_str = dir.to_string_lossy().to_string();
+                let summary = scanner::scan_directory(conn, &dir_str, &self.options, &self.cancel, &mut |_| {})?;
+                changed |= summary.assets_found + summary.updated + summary.pruned > 0;
+            }
+
+            if !self.extensions.is_empty() && target.exists() {
+                let target_str = target.to_string_lossy().to_string();
+                let summary = scanner::scan_individual_directory(conn, &target_str, &self.extensions, &self.options, &self.cancel, &mut |_| {})?;
+                changed |= summary.assets_found + summary.pruned > 0;
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Delete the rows of [table] at [path] or anywhere below it.
+fn remove_under(conn: &Connection, table: &str, path: &str) -> SqlResult<usize> {
+    conn.execute(
+        &format!("DELETE FROM {} WHERE {}", table, roots::under("path", 1)),
+        params![roots::slashed(path)],
+    )
+}
+
+/// Drop every path that lives under another path of the set; scanning the parent covers it.
+fn top_level(paths: &BTreeSet<PathBuf>) -> Vec<PathBuf> {
+    let mut result: Vec<PathBuf> = Vec::new();
+    // BTreeSet order puts a directory right before the paths inside it.
+    for path in paths {
+        if !result.last().is_some_and(|last| path.starts_with(last)) {
+            result.push(path.clone());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+    use notify::event::Flag;
+
+    fn worker(root: &Path) -> Worker {
+        Worker {
+            db_path: String::new(),
+            root: root.to_path_buf(),
+            options: ScanOptions {
+                preview_file_names: vec!["Preview".to_string()],
+                use_first_image: false,
+                use_folder_name: true,
+                ignore_patterns: Vec::new(),
+                ignore_root: Some(root.to_path_buf()),
+                parallel: false,
+                disk_usage: false,
+            },
+            extensions: Vec::new(),
+            cancel: CancelToken::default(),
+        }
+    }
+
+    #[test]
+    fn dropped_events_rescan_the_whole_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        let pack = dir.path().join("Deep").join("Pack");
+        std::fs::create_dir_all(&pack).unwrap();
+        std::fs::write(pack.join("Preview.png"), b"png").unwrap();
+
+        let mut changes = Changes::default();
+        changes.add(notify::Event::new(EventKind::Other).set_flag(Flag::Rescan));
+        assert!(!changes.is_empty());
+        assert!(worker(dir.path()).apply(&mut conn, changes).unwrap());
+        let found: String = conn.query_row("SELECT path FROM assets", [], |row| row.get(0)).unwrap();
+        assert_eq!(found, pack.to_string_lossy());
+    }
+
+    #[test]
+    fn removals_match_either_separator() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r"CREATE TABLE assets (path TEXT);
+              INSERT INTO assets (path) VALUES ('C:/lib\Kit'), ('C:/lib\Kit\Trees'), ('C:/lib\Kits');",
+        )
+        .unwrap();
+        assert_eq!(remove_under(&conn, "assets", r"C:\lib\Kit").unwrap(), 2);
+    }
+}